@@ -1,6 +1,8 @@
 #version 450
 
-const int MAX_LIGHTS = 10;
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS 10
+#endif
 
 layout(location = 0) in vec3 v_Normal;
 layout(location = 1) in vec4 v_Position;
@@ -32,9 +34,9 @@ float fetch_shadow(int light_id, vec4 homogeneous_coords) {
     if (homogeneous_coords.w <= 0.0) {
         return 1.0;
     }
-    // compute texture coordinates for shadow lookup
+    // compute texture coordinates for shadow lookup, texture v grows downwards
     vec4 light_local = vec4(
-        (homogeneous_coords.xy/homogeneous_coords.w + 1.0) / 2.0,
+        homogeneous_coords.xy * vec2(0.5, -0.5) / homogeneous_coords.w + 0.5,
         light_id,
         homogeneous_coords.z / homogeneous_coords.w
     );
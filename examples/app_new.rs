@@ -1,5 +1,45 @@
 use glsl_naga::application::*;
 #[tokio::main]
 async fn main(){
-    Application::new().await;
-}
\ No newline at end of file
+    // --hot-reload: 从 assets/ 读取着色器,修改后自动重建管线
+    let asset_mode = if std::env::args().any(|arg| arg == "--hot-reload") {
+        AssetMode::Runtime(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets"))
+    } else {
+        AssetMode::Embedded
+    };
+    // --pentagon: 显示最初的五边形示例,而不是阴影场景
+    let scene = if std::env::args().any(|arg| arg == "--pentagon") {
+        Scene::Pentagon
+    } else {
+        Scene::Shadow
+    };
+    // --no-vsync: 用 Immediate 呈现模式,不支持时退回 Fifo
+    let config = if std::env::args().any(|arg| arg == "--no-vsync") {
+        AppConfig::default().with_present_mode(wgpu::PresentMode::Immediate)
+    } else {
+        AppConfig::default()
+    };
+    // --fps <n>: 限制帧率,默认只由垂直同步控制
+    let args: Vec<String> = std::env::args().collect();
+    let config = match args.iter().position(|arg| arg == "--fps").and_then(|i| args.get(i + 1)) {
+        Some(fps) => config.with_frame_rate(FrameRate::Target(fps.parse().expect("--fps takes a number"))),
+        None => config,
+    };
+    // --fullscreen: 以无边框全屏启动,Alt+Enter 切换
+    let target = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target");
+    let window = WindowConfig {
+        title: format!("Hello Wgpu! ({})", scene.name()),
+        icon: Some(std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets").join("icon.png")),
+        start_fullscreen: std::env::args().any(|arg| arg == "--fullscreen"),
+        settings_file: Some(target.join("window-settings.txt")),
+        ..Default::default()
+    };
+    Application::builder()
+        .window(window)
+        .asset_mode(asset_mode)
+        .scene(scene)
+        .config(config)
+        .shader_cache_dir(target.join("shader-cache"))
+        .run(DemoApp::default())
+        .await;
+}
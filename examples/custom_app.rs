@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use glsl_naga::application::*;
+use glsl_naga::scene::{EntityDesc, EntityId, MeshKind};
+
+// 自定义程序:按钮生成立方体,空格删除最早的一个,其余交给默认实现
+#[derive(Debug, Default)]
+struct CubeSpawner {
+    spawned: Vec<EntityId>,
+    spawn_requested: bool,
+    elapsed: Duration,
+}
+
+impl App for CubeSpawner {
+    fn update(&mut self, ctx: &mut AppContext, dt: Duration) {
+        self.elapsed += dt;
+        if std::mem::take(&mut self.spawn_requested) {
+            let t = self.elapsed.as_secs_f32();
+            let desc = EntityDesc {
+                mesh: MeshKind::Cube,
+                mx_world: cgmath::Matrix4::from_translation(cgmath::Vector3::new(3.0 * t.cos(), 1.0, 3.0 * t.sin())),
+                rotation_speed: 1.0,
+                color: wgpu::Color::WHITE,
+            };
+            match ctx.renderer.spawn_entity(desc) {
+                Ok(id) => self.spawned.push(id),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, _renderer: &glsl_naga::renderer::Renderer) {
+        egui::Window::new("Cubes").show(ctx, |ui| {
+            ui.label(format!("{} spawned", self.spawned.len()));
+            self.spawn_requested |= ui.button("Spawn").clicked();
+        });
+    }
+
+    fn on_event(&mut self, ctx: &mut AppContext, event: &winit::event::WindowEvent) {
+        if let winit::event::WindowEvent::KeyboardInput { event: key, .. } = event {
+            let space = winit::keyboard::PhysicalKey::Code(winit::keyboard::KeyCode::Space);
+            if key.state.is_pressed() && key.physical_key == space && !self.spawned.is_empty() {
+                let id = self.spawned.remove(0);
+                let _ = ctx.renderer.despawn_entity(id);
+            }
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    Application::builder()
+        .title("Cube spawner")
+        .size(1280, 720)
+        .run(CubeSpawner::default())
+        .await;
+}
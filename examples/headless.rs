@@ -0,0 +1,22 @@
+use glsl_naga::headless::*;
+use glsl_naga::image::write_png;
+
+// 无窗口渲染一帧并读回 CPU,可在没有显示器的 CI 中运行
+#[tokio::main]
+async fn main() {
+    let options = HeadlessOptions {
+        force_fallback_adapter: std::env::args().any(|arg| arg == "--software"),
+        ..Default::default()
+    };
+    let mut headless = match HeadlessRenderer::new(options).await {
+        Ok(headless) => headless,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let image = headless.render().expect("failed to render");
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("headless.png");
+    write_png(&path, &image).expect("failed to write png");
+    println!("{}x{} frame written to {}", image.width, image.height, path.display());
+}
@@ -2,7 +2,11 @@ use glsl_naga::utils::*;
 fn main() {
     let glsl = include_str!("../assets/shader.vert");
 
-    let code = glsl_to_wgsl(&glsl, naga::ShaderStage::Vertex);
-
-    println!("{}", code);
-}
\ No newline at end of file
+    match try_glsl_to_wgsl(glsl, naga::ShaderStage::Vertex) {
+        Ok(code) => println!("{}", code),
+        Err(e) => {
+            e.emit_to_stderr("assets/shader.vert");
+            std::process::exit(1);
+        }
+    }
+}
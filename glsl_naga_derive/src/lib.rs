@@ -0,0 +1,76 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitInt};
+
+/// Derives `glsl_naga::vertex_layout::VertexLayout` for a struct with named fields.
+///
+/// Fields take shader locations 0, 1, 2… in declaration order. Per field:
+/// - `#[vertex(location = N)]` sets the location, later fields continue from N + 1.
+/// - `#[vertex(format = Snorm8x4)]` overrides the format picked from the field type.
+/// - `#[vertex(skip)]` leaves the field out of the layout.
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "VertexLayout cannot be derived for generic structs"));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "VertexLayout needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "VertexLayout can only be derived for structs")),
+    };
+
+    let name = &input.ident;
+    let krate = quote!(::glsl_naga::vertex_layout);
+    let mut location = 0u32;
+    let mut attributes = Vec::new();
+    for field in fields {
+        let mut format: Option<Ident> = None;
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("location") {
+                    location = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("format") {
+                    format = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("skip") {
+                    skip = true;
+                } else {
+                    return Err(meta.error("expected `location`, `format` or `skip`"));
+                }
+                Ok(())
+            })?;
+        }
+        if skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().expect("named field");
+        let ty = &field.ty;
+        let format = match format {
+            Some(format) => quote!(#krate::wgpu::VertexFormat::#format),
+            None => quote!(<#ty as #krate::VertexAttributeType>::FORMAT),
+        };
+        attributes.push(quote! {
+            #krate::wgpu::VertexAttribute {
+                format: #format,
+                offset: ::core::mem::offset_of!(#name, #ident) as #krate::wgpu::BufferAddress,
+                shader_location: #location,
+            }
+        });
+        location += 1;
+    }
+
+    Ok(quote! {
+        impl #krate::VertexLayout for #name {
+            const ATTRIBUTES: &'static [#krate::wgpu::VertexAttribute] = &[#(#attributes),*];
+        }
+    })
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::renderer::Renderer;
+
+/// What an [`App`] hook gets to work with: the renderer, the window and a way to quit.
+#[derive(Debug)]
+pub struct AppContext<'a> {
+    pub renderer: &'a mut Renderer,
+    pub window: &'a Window,
+    close_requested: &'a mut bool,
+}
+
+impl<'a> AppContext<'a> {
+    pub(crate) fn new(renderer: &'a mut Renderer, window: &'a Window, close_requested: &'a mut bool) -> Self {
+        Self {
+            renderer,
+            window,
+            close_requested,
+        }
+    }
+
+    /// Closes the window and leaves the event loop once the current event is handled.
+    pub fn exit(&mut self) {
+        *self.close_requested = true;
+    }
+}
+
+/// User code plugged into [`crate::application::Application`]. Every hook has a default,
+/// so an app only implements what it needs; see [`crate::demo::DemoApp`].
+pub trait App {
+    /// Called once, after the window and the scene's passes exist and before the first frame.
+    fn init(&mut self, _ctx: &mut AppContext) {}
+
+    /// One fixed simulation step, after the renderer advanced its own scene by `dt`.
+    fn update(&mut self, _ctx: &mut AppContext, _dt: Duration) {}
+
+    /// Records the frame into `view`, which has the renderer's format. The default draws the
+    /// renderer's scene; the egui overlay is drawn on top afterwards.
+    fn render(&mut self, ctx: &mut AppContext, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        ctx.renderer.render(encoder, view);
+    }
+
+    /// Adds egui windows to the overlay. The renderer is read-only while the UI is built,
+    /// so changes have to wait for `update` or `on_event`.
+    fn ui(&mut self, _ctx: &egui::Context, _renderer: &Renderer) {}
+
+    /// Every event of the main window that egui did not consume, except redraws. Tool
+    /// windows handle their own events.
+    fn on_event(&mut self, _ctx: &mut AppContext, _event: &WindowEvent) {}
+}
+
+impl std::fmt::Debug for dyn App {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("dyn App")
+    }
+}
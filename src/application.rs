@@ -1,63 +1,211 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use wgpu::util::DeviceExt;
+use std::time::Instant;
+use winit::event::WindowEvent;
 use winit::event_loop::{EventLoop, EventLoopWindowTarget};
-use winit::window::{Window, WindowBuilder};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{Window, WindowBuilder, WindowId};
+
+pub use crate::app::{App, AppContext};
+pub use crate::config::{AppConfig, FullscreenMode, WindowConfig};
+use crate::config::{load_icon, render_format, WindowSettings};
+pub use crate::demo::DemoApp;
+pub use crate::game_loop::FrameRate;
+use crate::game_loop::{FixedTimestep, FramePacer};
+use crate::gui_tools::GuiRenderer;
+use crate::hot_reload::FileWatcher;
+use crate::inspector::{Inspector, InspectorEdit, SurfaceInfo};
+use crate::msaa::ColorTarget;
+use crate::shader_editor::{EditorAction, ShaderEditor};
+use crate::viewport::Viewport;
+pub use crate::renderer::AssetMode;
+use crate::renderer::Renderer;
+pub use crate::renderer::Scene;
+
+/// Window and renderer settings for [`Application::builder`], finished by [`Self::run`].
+#[derive(Debug, Clone)]
+pub struct ApplicationBuilder {
+    window: WindowConfig,
+    asset_mode: AssetMode,
+    scene: Scene,
+    config: AppConfig,
+    shader_cache_dir: Option<PathBuf>,
+}
 
-use crate::data_stuct::{Pass, State};
-use crate::utils::glsl_to_wgsl;
+impl Default for ApplicationBuilder {
+    fn default() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            asset_mode: AssetMode::Embedded,
+            scene: Scene::default(),
+            config: AppConfig::default(),
+            shader_cache_dir: None,
+        }
+    }
+}
+
+impl ApplicationBuilder {
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.window.title = title.into();
+        self
+    }
+
+    /// Inner size in logical pixels.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.window.size = (width, height);
+        self
+    }
+
+    pub fn window(mut self, window: WindowConfig) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn asset_mode(mut self, asset_mode: AssetMode) -> Self {
+        self.asset_mode = asset_mode;
+        self
+    }
+
+    pub fn scene(mut self, scene: Scene) -> Self {
+        self.scene = scene;
+        self
+    }
+
+    pub fn config(mut self, config: AppConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Keeps translated shaders in `dir` so the next launch skips naga. Off by default.
+    pub fn shader_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.shader_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Opens the window and runs `app` in it until the window is closed.
+    pub async fn run(self, app: impl App + 'static) {
+        let event_loop = EventLoop::new().unwrap();
+        println!("creating");
+        let mut application = match Application::create(&event_loop, self, Box::new(app)).await {
+            Ok(application) => application,
+            Err(e) => {
+                eprintln!("Failed to create the application: {e}");
+                return;
+            }
+        };
+        application.init_render_passes();
+        application.init_app();
+        println!("created");
+        event_loop.run(move |event, elwt| {
+            application.event_handler(event, elwt);
+        }).expect("Failed to run event loop");
+    }
+}
+
+/// Built-in panels that can be detached from the main window into windows of their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ToolWindow {
+    /// Shift+F1
+    Inspector,
+    /// Shift+F2
+    ShaderEditor,
+}
+
+impl ToolWindow {
+    pub fn title(self) -> &'static str {
+        match self {
+            ToolWindow::Inspector => "Inspector",
+            ToolWindow::ShaderEditor => "Shader editor",
+        }
+    }
+
+    /// Initial inner size in logical pixels.
+    fn size(self) -> (u32, u32) {
+        match self {
+            ToolWindow::Inspector => (420, 640),
+            ToolWindow::ShaderEditor => (900, 600),
+        }
+    }
+}
 
-#[allow(dead_code)]
 #[derive(Debug)]
-struct WindowState {
-    close_requested: bool,
-    view_updated: bool,
-    factor: f64,
+struct OpenToolWindow {
+    tool: ToolWindow,
+    viewport: Viewport,
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Application {
-    window: Arc<Window>,
-    window_state: WindowState,
-    surface: wgpu::Surface<'static>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
+    /// Where the scene is drawn. Closing it quits, closing a tool window does not.
+    main: Viewport,
+    /// Secondary windows drawing only egui, on the same device as the main one.
+    tool_windows: HashMap<WindowId, OpenToolWindow>,
+    window_config: WindowConfig,
+    /// Kept to set up the surfaces of tool windows.
+    app_config: AppConfig,
+    /// Kept to create surfaces for new windows and after the device is lost.
+    instance: wgpu::Instance,
+    close_requested: bool,
     last_frame_time: Instant,
-    states: Option<State>,
+    timestep: FixedTimestep,
+    frame_pacer: FramePacer,
+    shader_watcher: Option<FileWatcher>,
+    renderer: Renderer,
+    app: Box<dyn App>,
+    inspector: Inspector,
+    shader_editor: ShaderEditor,
 }
 
 
 impl Application {
+    pub fn builder() -> ApplicationBuilder {
+        ApplicationBuilder::default()
+    }
+
+    /// Runs [`DemoApp`] with the default settings.
+    #[allow(clippy::new_ret_no_self)]
     pub async fn new() {
-        let event_loop = EventLoop::new().unwrap();
-        println!("creating");
-        let mut app = Self::create(&event_loop).await;
-        app.init_render_passes();
-        println!("created");
-        event_loop.run(move |event, elwt| {
-            app.event_handler(event, elwt);
-        }).expect("Failed to run event loop");
+        Self::with_asset_mode(AssetMode::Embedded).await;
+    }
+
+    pub async fn with_asset_mode(asset_mode: AssetMode) {
+        Self::with_scene(asset_mode, Scene::default()).await;
+    }
+
+    pub async fn with_scene(asset_mode: AssetMode, scene: Scene) {
+        Self::with_config(asset_mode, scene, AppConfig::default()).await;
     }
 
-    async fn create(event_loop: &EventLoop<()>) -> Self {
+    pub async fn with_config(asset_mode: AssetMode, scene: Scene, app_config: AppConfig) {
+        Self::builder()
+            .asset_mode(asset_mode)
+            .scene(scene)
+            .config(app_config)
+            .run(DemoApp::default())
+            .await;
+    }
+
+    async fn create(
+        event_loop: &EventLoop<()>,
+        builder: ApplicationBuilder,
+        app: Box<dyn App>,
+    ) -> Result<Self, String> {
+        let ApplicationBuilder {
+            window: window_config,
+            asset_mode,
+            scene,
+            config: app_config,
+            shader_cache_dir,
+        } = builder;
         println!("Creating Application");
-        let mut window_state = WindowState {
-            close_requested: false,
-            view_updated: false,
-            factor: 1.0,
-        };
 
-        let builder = WindowBuilder::new();
-        let window = Arc::new(builder
-            .with_title("Hello Wgpu!")
-            .with_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0))
-            .with_min_inner_size(winit::dpi::LogicalSize::new(1024.0, 768.0))
-            .build(&event_loop).unwrap());
+        let window = Arc::new(build_window(&window_config, event_loop));
+        if window_config.start_fullscreen {
+            window.set_fullscreen(fullscreen(&window, window_config.fullscreen));
+        }
         let size = window.inner_size();
-        window_state.factor = window.scale_factor();
 
         // instance 变量是 GPU 实例
         // Backends::all 对应 Vulkan、Metal、DX12、WebGL 等所有后端图形驱动
@@ -65,7 +213,7 @@ impl Application {
             backends: wgpu::Backends::all(),
             ..Default::default()
         });
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = instance.create_surface(window.clone()).map_err(|e| e.to_string())?;
 
         let power_pref = wgpu::PowerPreference::default();
         let adapter = instance
@@ -75,7 +223,7 @@ impl Application {
                 compatible_surface: Some(&surface),
             })
             .await
-            .expect("Failed to find an appropriate adapter");
+            .ok_or("Failed to find an appropriate adapter")?;
 
         let features = wgpu::Features::empty();
         let (device, queue) = adapter
@@ -88,294 +236,538 @@ impl Application {
                 None,
             )
             .await
-            .expect("Failed to create device");
+            .map_err(|e| format!("Failed to create device: {e}"))?;
 
         let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let selected_format = wgpu::TextureFormat::Bgra8UnormSrgb;
-        let swapchain_format = swapchain_capabilities
-            .formats
-            .iter()
-            .find(|d| **d == selected_format)
-            .expect("failed to select proper surface texture format!");
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: *swapchain_format,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            desired_maximum_frame_latency: 0,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
-            view_formats: vec![],
-            width: size.width,
-            height: size.height,
-        };
+        let config = app_config.surface_configuration(&swapchain_capabilities, (size.width, size.height))?;
 
         surface.configure(&device, &config);
-        surface.get_current_texture().unwrap();
-        let app = Self {
-            window,
-            window_state,
-            surface,
+        let mut renderer = Renderer::new(
+            adapter,
             device,
             queue,
-            config,
-            size,
-            last_frame_time: Instant::now(),
-            states: None,
+            render_format(&config),
+            (size.width, size.height),
+            asset_mode,
+            scene,
+        );
+        renderer.set_shader_cache_dir(shader_cache_dir);
+
+        let gui = GuiRenderer::new(renderer.device(), renderer.format(), None, renderer.sample_count(), &window);
+
+        let shader_watcher = match renderer.asset_mode() {
+            AssetMode::Embedded => None,
+            AssetMode::Runtime(dir) => FileWatcher::new(dir)
+                .map_err(|e| eprintln!("not watching {} for shader changes: {e}", dir.display()))
+                .ok(),
         };
-        app
+        Ok(Self {
+            main: Viewport::from_parts(window, surface, config, gui),
+            tool_windows: HashMap::new(),
+            window_config,
+            instance,
+            close_requested: false,
+            last_frame_time: Instant::now(),
+            timestep: FixedTimestep::new(app_config.update_rate),
+            frame_pacer: FramePacer::new(app_config.frame_rate),
+            app_config,
+            shader_watcher,
+            app,
+            inspector: Inspector::default(),
+            shader_editor: ShaderEditor::default(),
+            renderer,
+        })
     }
 
+    /// Routes window events by `WindowId`, to the main window or one of the tool windows.
     pub fn event_handler(&mut self, event: winit::event::Event<()>, elwt: &EventLoopWindowTarget<()>) {
-        elwt.set_control_flow(winit::event_loop::ControlFlow::Poll);
         match event {
-            winit::event::Event::WindowEvent { event, .. } => {
-                match event {
-                    winit::event::WindowEvent::CloseRequested => {
-                        self.window_state.close_requested = true;
-                    }
-                    winit::event::WindowEvent::Resized(new_size) => {
-                        if new_size.width > 0 && new_size.height > 0 {
-                            self.size = new_size;
-                            self.resize();
-                            self.window_state.view_updated = true;
-                        } else {
-                            self.window_state.view_updated = false;
-                        }
-                    }
-                    winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
-                        self.window_state.factor = scale_factor;
-                    }
-                    winit::event::WindowEvent::RedrawRequested => {
-                        if self.window_state.view_updated {
-                            self.redraw();
-                        }
-                    }
-                    _ => {}
-                }
+            winit::event::Event::WindowEvent { window_id, event } if window_id == self.main.window.id() => {
+                self.main_window_event(event, elwt);
             }
-            winit::event::Event::AboutToWait { .. } => {
-                if self.window_state.close_requested {
-                    elwt.exit();
+            winit::event::Event::WindowEvent { window_id, event } => {
+                self.tool_window_event(window_id, event);
+            }
+            winit::event::Event::AboutToWait if self.close_requested => {
+                self.save_window_settings();
+                elwt.exit();
+            }
+            winit::event::Event::AboutToWait if self.renderer.is_device_lost() => {
+                self.recover_lost_device();
+            }
+            winit::event::Event::AboutToWait => {
+                let shaders_changed = self.shader_watcher.as_ref().is_some_and(|watcher| {
+                    watcher
+                        .changed_files()
+                        .iter()
+                        .any(|path| matches!(path.extension().and_then(|e| e.to_str()), Some("vert" | "frag" | "glsl")))
+                });
+                if shaders_changed {
+                    self.reload_shaders();
                 }
+                self.schedule_frame(elwt);
             }
             _ => {}
         }
     }
 
-    pub fn init_render_passes(&mut self) {
-        println!("Initializing");
-        let vs_code = glsl_to_wgsl(include_str!("../assets/shader.vert"), naga::ShaderStage::Vertex);
-        let vs_module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(vs_code.into()),
-        });
-        let fs_code = glsl_to_wgsl(include_str!("../assets/shader.frag"), naga::ShaderStage::Fragment);
-        let fs_module = self.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(fs_code.into()),
-        });
-
-        let render_pipeline_layout = self.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
-
-        #[allow(dead_code)]
-        #[derive(Debug, Clone, Copy)]
-        struct Vertex {
-            position: [f32; 3],
-            color: [f32; 3],
+    fn main_window_event(&mut self, event: WindowEvent, elwt: &EventLoopWindowTarget<()>) {
+        let consumed_by_gui = self.main.handle_event(self.renderer.device(), &event);
+        let shift = self.main.modifiers.shift_key();
+        match event {
+            WindowEvent::CloseRequested => {
+                self.close_requested = true;
+            }
+            WindowEvent::Resized(_) => {
+                if self.main.visible {
+                    self.resize();
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if self.main.visible {
+                    self.redraw();
+                }
+            }
+            // egui 已经处理的输入(例如点击面板、在着色器编辑器里打字)不再当作快捷键,也不传给用户程序
+            _ if consumed_by_gui => {}
+            WindowEvent::KeyboardInput { event: ref key, .. }
+                if key.state.is_pressed() && key.physical_key == PhysicalKey::Code(KeyCode::F1) =>
+            {
+                match shift {
+                    true => self.open_tool_window(elwt, ToolWindow::Inspector),
+                    false => self.inspector.toggle(),
+                }
+            }
+            WindowEvent::KeyboardInput { event: ref key, .. }
+                if key.state.is_pressed() && key.physical_key == PhysicalKey::Code(KeyCode::F2) =>
+            {
+                match shift {
+                    true => self.open_tool_window(elwt, ToolWindow::ShaderEditor),
+                    false => self.shader_editor.toggle(&self.renderer),
+                }
+            }
+            WindowEvent::KeyboardInput { event: ref key, .. }
+                if key.state.is_pressed()
+                    && !key.repeat
+                    && self.main.modifiers.alt_key()
+                    && key.physical_key == PhysicalKey::Code(KeyCode::Enter) =>
+            {
+                self.toggle_fullscreen();
+            }
+            event => {
+                let (app, mut ctx) = self.split();
+                app.on_event(&mut ctx, &event);
+            }
         }
+    }
 
-        const VERTICES: &[Vertex] = &[
-            Vertex {
-                position: [-0.0868241, 0.49240386, 0.0],
-                color: [0.5, 0.0, 0.5],
-            }, // A
-            Vertex {
-                position: [-0.49513406, 0.06958647, 0.0],
-                color: [0.5, 0.0, 0.5],
-            }, // B
-            Vertex {
-                position: [-0.21918549, -0.44939706, 0.0],
-                color: [0.5, 0.0, 0.5],
-            }, // C
-            Vertex {
-                position: [0.35966998, -0.3473291, 0.0],
-                color: [0.5, 0.0, 0.5],
-            }, // D
-            Vertex {
-                position: [0.44147372, 0.2347359, 0.0],
-                color: [0.5, 0.0, 0.5],
-            }, // E
-        ];
-
-        const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
-
-        let vertex_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: crate::utils::cast_slice(VERTICES),
-                usage: wgpu::BufferUsages::VERTEX,
+    fn tool_window_event(&mut self, window_id: WindowId, event: WindowEvent) {
+        let Some(tool) = self.tool_windows.get_mut(&window_id) else {
+            return;
+        };
+        tool.viewport.handle_event(self.renderer.device(), &event);
+        match event {
+            WindowEvent::CloseRequested => {
+                self.tool_windows.remove(&window_id);
             }
-        );
+            WindowEvent::RedrawRequested => {
+                if tool.viewport.visible {
+                    self.redraw_tool_window(window_id);
+                }
+            }
+            // 主窗口最小化时工具窗口仍要响应输入
+            _ => tool.viewport.window.request_redraw(),
+        }
+    }
 
-        let index_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: crate::utils::cast_slice(INDICES),
-                usage: wgpu::BufferUsages::INDEX,
+    /// Shows `tool` in a window of its own, or focuses that window when it is already open.
+    /// While detached the panel is not drawn over the scene.
+    pub fn open_tool_window(&mut self, elwt: &EventLoopWindowTarget<()>, tool: ToolWindow) {
+        if let Some(open) = self.tool_windows.values().find(|open| open.tool == tool) {
+            open.viewport.window.focus_window();
+            return;
+        }
+        let (width, height) = tool.size();
+        let icon = self.window_config.icon.as_deref().and_then(|path| load_icon(path).ok());
+        let window = WindowBuilder::new()
+            .with_title(format!("{} - {}", tool.title(), self.window_config.title))
+            .with_inner_size(winit::dpi::LogicalSize::new(width, height))
+            .with_window_icon(icon)
+            .build(elwt);
+        let viewport = window
+            .map_err(|e| e.to_string())
+            .and_then(|window| Viewport::new(&self.instance, Arc::new(window), &self.renderer, &self.app_config));
+        match viewport {
+            Ok(viewport) => {
+                if tool == ToolWindow::ShaderEditor {
+                    self.shader_editor.load_once(&self.renderer);
+                }
+                self.tool_windows.insert(viewport.window.id(), OpenToolWindow { tool, viewport });
             }
-        );
+            Err(e) => eprintln!("failed to open the {} window: {e}", tool.title()),
+        }
+    }
 
-        let num_indices = INDICES.len() as u32;
+    fn is_detached(&self, tool: ToolWindow) -> bool {
+        self.tool_windows.values().any(|open| open.tool == tool)
+    }
 
-        let vertex_buffer_descriptor = wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x3,
-                    offset: 0,
-                    shader_location: 0,
-                },
-                wgpu::VertexAttribute {
-                    format: wgpu::VertexFormat::Float32x4,
-                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                },
-            ],
-        };
+    /// The user app and a context for it, borrowed from disjoint fields.
+    fn split(&mut self) -> (&mut dyn App, AppContext<'_>) {
+        let ctx = AppContext::new(&mut self.renderer, &self.main.window, &mut self.close_requested);
+        (self.app.as_mut(), ctx)
+    }
 
-        let render_pipeline = self.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &vs_module,
-                entry_point: "main",
-                compilation_options: Default::default(),
-                buffers: &[vertex_buffer_descriptor],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fs_module,
-                compilation_options: Default::default(),
-                entry_point: "main",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: self.config.format.add_srgb_suffix(),
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: None, // 1.
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None, // 5.
-        });
+    fn init_app(&mut self) {
+        let (app, mut ctx) = self.split();
+        app.init(&mut ctx);
+    }
+
+    pub fn init_render_passes(&mut self) {
+        if self.renderer.init_render_passes().is_err() {
+            self.show_shader_error();
+        }
+    }
 
-        let pass = Pass {
-            pipeline: render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            index_count: num_indices,
+    /// Rebuilds the forward pipeline after a shader file changed, keeping the old one on failure.
+    pub fn reload_shaders(&mut self) {
+        match self.renderer.reload_shaders() {
+            Ok(()) => self.main.window.set_title(&self.window_config.title),
+            Err(_) => self.show_shader_error(),
+        }
+    }
+
+    fn show_shader_error(&self) {
+        self.main.window.set_title(&format!("{} - shader error, see console", self.window_config.title));
+    }
+
+    /// Alt+Enter: switches between the window and `window_config.fullscreen`.
+    pub fn toggle_fullscreen(&mut self) {
+        let next = match self.main.window.fullscreen() {
+            Some(_) => None,
+            None => fullscreen(&self.main.window, self.window_config.fullscreen),
         };
+        self.main.window.set_fullscreen(next);
+    }
 
-        self.states = Some(State {
-            forward_pass: pass,
-        });
+    /// Remembers where the window was for the next run. Fullscreen placement is not kept.
+    fn save_window_settings(&self) {
+        let Some(path) = &self.window_config.settings_file else {
+            return;
+        };
+        let window = &self.main.window;
+        if window.fullscreen().is_some() || window.is_minimized() == Some(true) {
+            return;
+        }
+        let size = window.inner_size();
+        let settings = WindowSettings {
+            position: window.outer_position().ok().map(|position| (position.x, position.y)),
+            size: (size.width, size.height),
+        };
+        if let Err(e) = settings.save(path) {
+            eprintln!("failed to save window settings to {}: {e}", path.display());
+        }
     }
 
-    pub fn redraw(&mut self) {
-        let now = Instant::now();
-        if now.duration_since(self.last_frame_time) < Duration::from_millis(16) {
+    /// The last shader compile error, cleared by the next successful reload.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.renderer.shader_error()
+    }
+
+    /// Changes the MSAA sample count at runtime, see [`Renderer::set_sample_count`].
+    pub fn set_sample_count(&mut self, sample_count: u32) -> Result<(), String> {
+        self.renderer.set_sample_count(sample_count)?;
+        self.main.gui.set_msaa_samples(self.renderer.device(), &self.main.window, sample_count);
+        Ok(())
+    }
+
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    /// Requests a redraw once the frame pacer says the next frame is due and sleeps until then,
+    /// instead of spinning. Nothing is requested while the main window is minimized.
+    fn schedule_frame(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        use winit::event_loop::ControlFlow;
+
+        if !self.main.visible {
+            elwt.set_control_flow(ControlFlow::Wait);
             return;
         }
-        println!("Redrawing");
+        if self.frame_pacer.frame_due(Instant::now()) {
+            self.main.window.request_redraw();
+            for open in self.tool_windows.values() {
+                open.viewport.window.request_redraw();
+            }
+        }
+        match self.frame_pacer.next_frame() {
+            Some(next_frame) => elwt.set_control_flow(ControlFlow::WaitUntil(next_frame)),
+            // 由 present 阻塞来控制帧率
+            None => elwt.set_control_flow(ControlFlow::Wait),
+        }
+    }
 
+    /// Runs the update steps that are due, then draws one frame blended between the last two.
+    pub fn redraw(&mut self) {
+        let now = Instant::now();
+        self.inspector.record_frame(now.duration_since(self.last_frame_time));
         self.last_frame_time = now;
+        self.frame_pacer.frame_drawn(now);
+        let step = self.timestep.step();
+        for _ in 0..self.timestep.advance(now) {
+            self.renderer.update(step);
+            let (app, mut ctx) = self.split();
+            app.update(&mut ctx, step);
+        }
+        self.renderer.set_interpolation_alpha(self.timestep.alpha());
 
-        if !self.window.is_visible().unwrap_or(false) {
+        if !self.main.window.is_visible().unwrap_or(false) || self.renderer.is_device_lost() {
             return;
         }
-        let frame = self.surface.get_current_texture().unwrap();
-        let surface_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let frame = match self.main.current_texture(self.renderer.device()) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(_) => {
+                eprintln!("Out of memory acquiring the next frame, shutting down");
+                self.close_requested = true;
+                return;
+            }
+        };
+        let surface_view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(self.renderer.format()),
+            ..Default::default()
+        });
 
-        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        let mut encoder = self.renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("Command Encoder"),
         });
+        let (app, mut ctx) = self.split();
+        app.render(&mut ctx, &mut encoder, &surface_view);
+
+        // egui 画在场景之上,和场景用同一个(多重采样)目标
+        let screen_descriptor = self.main.screen_descriptor();
+        let inspector_detached = self.is_detached(ToolWindow::Inspector);
+        let editor_detached = self.is_detached(ToolWindow::ShaderEditor);
+        let shader_error = self.renderer.shader_error();
+        let renderer = &self.renderer;
+        let app = &mut self.app;
+        let inspector = &mut self.inspector;
+        let shader_editor = &mut self.shader_editor;
+        let mut edits = Vec::new();
+        let mut editor_action = None;
+        let (entities, lights) = match inspector.open && !inspector_detached {
+            true => (self.renderer.entities(), self.renderer.lights()),
+            false => Default::default(),
+        };
+        let info = surface_info(&self.renderer, &self.main.config);
+        self.main.gui.draw(
+            self.renderer.device(),
+            self.renderer.queue(),
+            &mut encoder,
+            &self.main.window,
+            self.renderer.color_target(&surface_view),
+            screen_descriptor,
+            |ctx| {
+                if let Some(report) = shader_error {
+                    egui::Window::new("Shader error").show(ctx, |ui| {
+                        ui.monospace(report);
+                    });
+                }
+                app.ui(ctx, renderer);
+                if !inspector_detached {
+                    edits = inspector.ui(ctx, &info, &entities, &lights);
+                }
+                if !editor_detached {
+                    editor_action = shader_editor.ui(ctx);
+                }
+            },
+        );
+        self.renderer.queue().submit(Some(encoder.finish()));
+        self.main.present(self.renderer.device(), frame);
+        self.apply_inspector_edits(edits);
+        if let Some(action) = editor_action {
+            self.apply_editor_action(action);
+        }
+    }
+
+    /// Draws the panel of a tool window. Its edits go to the same scene as the overlay's.
+    fn redraw_tool_window(&mut self, window_id: WindowId) {
+        if self.renderer.is_device_lost() {
+            return;
+        }
+        let Some(OpenToolWindow { tool, viewport }) = self.tool_windows.get_mut(&window_id) else {
+            return;
+        };
+        let tool = *tool;
+        let frame = match viewport.current_texture(self.renderer.device()) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return,
+            Err(_) => {
+                eprintln!("Out of memory acquiring the next frame, shutting down");
+                self.close_requested = true;
+                return;
+            }
+        };
+        let view = frame.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(viewport.format()),
+            ..Default::default()
+        });
+        let mut encoder = self.renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Tool Window Encoder"),
+        });
+        // 工具窗口没有场景,先清屏再画 egui
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tool Window Clear"),
+            color_attachments: &[Some(ColorTarget::new(&view, None).attachment(wgpu::LoadOp::Clear(wgpu::Color::BLACK)))],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        let inspector = &mut self.inspector;
+        let shader_editor = &mut self.shader_editor;
+        let mut edits = Vec::new();
+        let mut editor_action = None;
+        let (entities, lights) = match tool {
+            ToolWindow::Inspector => (self.renderer.entities(), self.renderer.lights()),
+            ToolWindow::ShaderEditor => Default::default(),
+        };
+        let info = surface_info(&self.renderer, &self.main.config);
+        let screen_descriptor = viewport.screen_descriptor();
+        viewport.gui.draw(
+            self.renderer.device(),
+            self.renderer.queue(),
+            &mut encoder,
+            &viewport.window,
+            ColorTarget::new(&view, None),
+            screen_descriptor,
+            |ctx| match tool {
+                ToolWindow::Inspector => edits = inspector.panel_ui(ctx, &info, &entities, &lights),
+                ToolWindow::ShaderEditor => editor_action = shader_editor.panel_ui(ctx),
+            },
+        );
+        self.renderer.queue().submit(Some(encoder.finish()));
+        viewport.present(self.renderer.device(), frame);
+        self.apply_inspector_edits(edits);
+        if let Some(action) = editor_action {
+            self.apply_editor_action(action);
+        }
+    }
+
+    /// Rebuilds the device, the surfaces and everything drawn on them. Gives up and exits if
+    /// no new device can be had.
+    fn recover_lost_device(&mut self) {
+        if let Err(e) = self.renderer.recreate_device() {
+            eprintln!("{e}, shutting down");
+            self.close_requested = true;
+            return;
+        }
+        if let Err(e) = self.main.recreate(&self.instance, &self.renderer) {
+            eprintln!("{e}, shutting down");
+            self.close_requested = true;
+            return;
+        }
+        // 工具窗口重建失败时只关掉那个窗口
+        let (instance, renderer) = (&self.instance, &self.renderer);
+        self.tool_windows.retain(|_, open| match open.viewport.recreate(instance, renderer) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("closing the {} window: {e}", open.tool.title());
+                false
+            }
+        });
+    }
 
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    // 这就是片元着色器中 @location(0) 标记指向的颜色附件
-                    Some(wgpu::RenderPassColorAttachment {
-                        view: &surface_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(
-                                wgpu::Color {
-                                    r: 0.1,
-                                    g: 0.2,
-                                    b: 0.3,
-                                    a: 1.0,
-                                }
-                            ),
-                            store: wgpu::StoreOp::Store
-                        }
-                    })
-                ],
-                ..Default::default()
-            });
-
-            // 新添加!
-            let Some(passes) = &self.states else { return };
-            render_pass.set_pipeline(&passes.forward_pass.pipeline); // 2.
-
-            let data = {
-                (&passes.forward_pass.vertex_buffer, &passes.forward_pass.index_buffer, passes.forward_pass.index_count)
+    /// Writes what was changed in the inspector back into the scene.
+    fn apply_inspector_edits(&mut self, edits: Vec<InspectorEdit>) {
+        for edit in edits {
+            let result = match edit {
+                InspectorEdit::Entity(id, desc) => {
+                    self.renderer.update_entity(id, |d| *d = desc).map_err(|e| e.to_string())
+                }
+                InspectorEdit::Light(id, desc) => self.renderer.update_light(id, |d| *d = desc).map_err(|e| e.to_string()),
+                InspectorEdit::SampleCount(count) => self.set_sample_count(count),
             };
-            render_pass.set_vertex_buffer(0,data.0.slice(..)); // 3.
-            render_pass.set_index_buffer(data.1.slice(..),wgpu::IndexFormat::Uint16); // 4.
-            render_pass.draw_indexed(0..data.2, 0, 0..1);
+            if let Err(e) = result {
+                eprintln!("inspector: {e}");
+            }
         }
-        self.queue.submit(Some(encoder.finish()));
-        frame.present();
-        self.window.request_redraw();
     }
 
+    /// Compiles the editor's sources into the running pipelines, or drops them on reset.
+    fn apply_editor_action(&mut self, action: EditorAction) {
+        match action {
+            EditorAction::Apply(sources) => {
+                for (name, source) in sources {
+                    self.renderer.set_shader_override(name, Some(source));
+                }
+            }
+            EditorAction::Reset => {
+                for file in self.renderer.shader_files() {
+                    self.renderer.set_shader_override(file.name, None);
+                }
+            }
+        }
+        self.reload_shaders();
+        if self.renderer.shader_error().is_none() {
+            self.shader_editor.load(&self.renderer);
+        }
+    }
+
+    /// Matches the scene's size-dependent targets to the main window.
     pub fn resize(&mut self) {
-        println!("Resizing");
-        self.size = self.window.inner_size();
-        if self.size.width > 0 && self.size.height > 0 {
-            self.config.width = self.size.width;
-            self.config.height = self.size.height;
-            self.surface.configure(&self.device, &self.config);
+        let size = self.main.window.inner_size();
+        self.renderer.resize(size.width, size.height);
+    }
+}
+
+fn build_window(config: &WindowConfig, event_loop: &EventLoopWindowTarget<()>) -> Window {
+    let mut builder = WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(winit::dpi::LogicalSize::new(config.size.0, config.size.1))
+        .with_resizable(config.resizable);
+    if let Some((width, height)) = config.min_size {
+        builder = builder.with_min_inner_size(winit::dpi::LogicalSize::new(width, height));
+    }
+    // 上次运行时的位置和大小
+    if let Some(settings) = config.settings_file.as_deref().and_then(WindowSettings::load) {
+        builder = builder.with_inner_size(winit::dpi::PhysicalSize::new(settings.size.0, settings.size.1));
+        if let Some((x, y)) = settings.position {
+            builder = builder.with_position(winit::dpi::PhysicalPosition::new(x, y));
         }
-        self.reconfigure_surface();
     }
+    if let Some(path) = &config.icon {
+        match load_icon(path) {
+            Ok(icon) => builder = builder.with_window_icon(Some(icon)),
+            Err(e) => eprintln!("no window icon: {e}"),
+        }
+    }
+    builder.build(event_loop).unwrap()
+}
 
-    fn reconfigure_surface(&mut self) {
-        self.size = self.window.inner_size();
-        self.config.width = self.size.width;
-        self.config.height = self.size.height;
-        self.surface.configure(&self.device, &self.config);
+/// The fullscreen state `mode` means on the monitor the window is on.
+fn fullscreen(window: &Window, mode: FullscreenMode) -> Option<winit::window::Fullscreen> {
+    let monitor = window.current_monitor();
+    let video_mode = match mode {
+        FullscreenMode::Borderless => None,
+        FullscreenMode::Exclusive => monitor.as_ref().and_then(|monitor| {
+            monitor
+                .video_modes()
+                .max_by_key(|video_mode| (video_mode.size().width * video_mode.size().height, video_mode.refresh_rate_millihertz()))
+        }),
+    };
+    Some(match video_mode {
+        Some(video_mode) => winit::window::Fullscreen::Exclusive(video_mode),
+        None => winit::window::Fullscreen::Borderless(monitor),
+    })
+}
+
+fn surface_info(renderer: &Renderer, config: &wgpu::SurfaceConfiguration) -> SurfaceInfo {
+    SurfaceInfo {
+        adapter: renderer.adapter().get_info(),
+        format: config.format,
+        present_mode: config.present_mode,
+        sample_count: renderer.sample_count(),
+        supported_sample_counts: renderer.supported_sample_counts(),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, ValueEnum};
+use glsl_naga::utils::*;
+use naga::back::{glsl, hlsl};
+use naga::ShaderStage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Target {
+    Wgsl,
+    Msl,
+    Hlsl,
+    Glsl,
+    GlslEs,
+    Spv,
+}
+
+impl Target {
+    fn lang(self) -> TargetLang {
+        match self {
+            Target::Wgsl => TargetLang::Wgsl,
+            Target::Msl => TargetLang::Msl,
+            Target::Hlsl => TargetLang::Hlsl,
+            Target::Glsl | Target::GlslEs => TargetLang::Glsl,
+            Target::Spv => TargetLang::SpirV,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Target::Wgsl => "wgsl",
+            Target::Msl => "metal",
+            Target::Hlsl => "hlsl",
+            Target::Glsl | Target::GlslEs => "glsl",
+            Target::Spv => "spv",
+        }
+    }
+}
+
+/// Translate shaders with naga. GLSL stages are inferred from .vert/.frag/.comp,
+/// .wgsl and .spv inputs are read as WGSL and SPIR-V.
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Input shader files.
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Output language.
+    #[arg(long, value_enum, default_value_t = Target::Wgsl)]
+    to: Target,
+
+    /// Output file for a single input, or a directory (existing, or ending in `/`) that gets
+    /// one file per input. `-` writes a single input to stdout. Files are named after the
+    /// input with the target extension appended, next to the input by default.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Parse and validate only, write nothing.
+    #[arg(long)]
+    validate_only: bool,
+
+    /// Preprocessor define for GLSL input, as NAME or NAME=VALUE.
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    define: Vec<String>,
+
+    /// Directory searched by `#include` after the including file's own directory.
+    #[arg(short = 'I', value_name = "DIR")]
+    include_dir: Option<PathBuf>,
+
+    /// GLSL output version. Defaults to 450 for glsl and 310 for glsl-es.
+    #[arg(long)]
+    glsl_version: Option<u16>,
+
+    /// MSL output version, e.g. 2.1.
+    #[arg(long, default_value = "2.1", value_parser = parse_msl_version)]
+    msl_version: (u8, u8),
+
+    /// HLSL shader model, e.g. 5_1 or 6_0.
+    #[arg(long, default_value = "5_1", value_parser = parse_shader_model)]
+    shader_model: hlsl::ShaderModel,
+
+    /// GLSL entry point to emit when the module has several.
+    #[arg(long)]
+    entry_point: Option<String>,
+}
+
+fn parse_msl_version(s: &str) -> Result<(u8, u8), String> {
+    let (major, minor) = s.split_once('.').ok_or("expected MAJOR.MINOR")?;
+    Ok((
+        major.parse().map_err(|e| format!("{e}"))?,
+        minor.parse().map_err(|e| format!("{e}"))?,
+    ))
+}
+
+fn parse_shader_model(s: &str) -> Result<hlsl::ShaderModel, String> {
+    use hlsl::ShaderModel::*;
+    [V5_0, V5_1, V6_0, V6_1, V6_2, V6_3, V6_4, V6_5, V6_6, V6_7]
+        .into_iter()
+        .find(|model| model.to_str() == s.replace('.', "_"))
+        .ok_or_else(|| format!("unknown shader model '{s}'"))
+}
+
+fn source_lang(path: &Path) -> Option<SourceLang> {
+    match path.extension()?.to_str()? {
+        "vert" => Some(SourceLang::Glsl(ShaderStage::Vertex)),
+        "frag" => Some(SourceLang::Glsl(ShaderStage::Fragment)),
+        "comp" => Some(SourceLang::Glsl(ShaderStage::Compute)),
+        "wgsl" => Some(SourceLang::Wgsl),
+        "spv" => Some(SourceLang::SpirV),
+        _ => None,
+    }
+}
+
+fn output_path(args: &Args, input: &Path) -> PathBuf {
+    let file_name = format!(
+        "{}.{}",
+        input.file_name().unwrap_or_default().to_string_lossy(),
+        args.to.extension()
+    );
+    match &args.output {
+        Some(out) if args.inputs.len() == 1 && !is_dir(out) => out.clone(),
+        Some(dir) => dir.join(file_name),
+        None => input.with_file_name(file_name),
+    }
+}
+
+/// `-o` names a directory when it already is one or ends in a separator.
+fn is_dir(path: &Path) -> bool {
+    path.is_dir() || path.as_os_str().to_string_lossy().ends_with(std::path::is_separator)
+}
+
+/// Inputs that would overwrite each other's output, e.g. `a/common.vert` and `b/common.vert` with `-o out/`.
+fn output_collisions(args: &Args) -> Vec<String> {
+    let mut by_output: HashMap<PathBuf, Vec<&Path>> = HashMap::new();
+    for input in &args.inputs {
+        by_output.entry(output_path(args, input)).or_default().push(input);
+    }
+    let mut collisions: Vec<String> = by_output
+        .into_iter()
+        .filter(|(_, inputs)| inputs.len() > 1)
+        .map(|(output, inputs)| {
+            let inputs: Vec<String> = inputs.iter().map(|input| input.display().to_string()).collect();
+            format!("{} would all be written to {}", inputs.join(", "), output.display())
+        })
+        .collect();
+    collisions.sort();
+    collisions
+}
+
+enum Failure {
+    /// Already printed as a diagnostic with source context.
+    Reported,
+    Message(String),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Message(message)
+    }
+}
+
+fn compile(args: &Args, options: &TranslateOptions, input: &Path) -> Result<(), Failure> {
+    let display = input.display().to_string();
+    let from = source_lang(input).ok_or_else(|| format!("{display}: unknown shader extension"))?;
+    let source = std::fs::read(input).map_err(|e| format!("{display}: {e}"))?;
+
+    let result = parse_module(&source, from, options)
+        .and_then(|(module, info)| match args.validate_only {
+            true => Ok(None),
+            false => write_module(&module, &info, args.to.lang(), options).map(Some),
+        });
+    let output = match result {
+        Ok(Some(output)) => output,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            e.emit_to_stderr(&display);
+            return Err(Failure::Reported);
+        }
+    };
+
+    if args.output.as_deref() == Some(Path::new("-")) {
+        use std::io::Write;
+        std::io::stdout()
+            .write_all(&output.to_bytes())
+            .map_err(|e| format!("stdout: {e}"))?;
+        return Ok(());
+    }
+    let path = output_path(args, input);
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    }
+    std::fs::write(&path, output.to_bytes()).map_err(|e| format!("{}: {e}", path.display()))?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.inputs.len() > 1 && args.output.as_deref() == Some(Path::new("-")) {
+        Args::command()
+            .error(ErrorKind::ArgumentConflict, "`--output -` takes a single input, pass a directory for several")
+            .exit();
+    }
+    if !args.validate_only {
+        let collisions = output_collisions(&args);
+        if !collisions.is_empty() {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("several inputs share an output name:\n  {}", collisions.join("\n  ")),
+                )
+                .exit();
+        }
+    }
+
+    let mut options = TranslateOptions::default();
+    for define in &args.define {
+        let (name, value) = define.split_once('=').unwrap_or((define, ""));
+        options.preprocess.defines.insert(name.to_string(), value.to_string());
+    }
+    let include_dir = args.include_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    options.preprocess.include_resolver = Some(Arc::new(FileIncludeResolver::new(include_dir)));
+    options.msl.lang_version = args.msl_version;
+    options.hlsl.shader_model = args.shader_model;
+    options.glsl.entry_point = args.entry_point.clone();
+    options.glsl.version = match args.to {
+        Target::GlslEs => glsl::Version::new_gles(args.glsl_version.unwrap_or(310)),
+        _ => glsl::Version::Desktop(args.glsl_version.unwrap_or(450)),
+    };
+
+    let mut failed = 0;
+    for input in &args.inputs {
+        match compile(&args, &options, input) {
+            Ok(()) => {}
+            Err(Failure::Reported) => failed += 1,
+            Err(Failure::Message(e)) => {
+                eprintln!("error: {e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        eprintln!("{failed} of {} shader(s) failed", args.inputs.len());
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
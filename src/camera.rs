@@ -0,0 +1,346 @@
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
+
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use winit::dpi::PhysicalPosition;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Keeps the controllers from flipping over the poles.
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+/// cgmath builds OpenGL clip space (z in -1..1), wgpu wants z in 0..1.
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { fovy: Deg<f32>, near: f32, far: f32 },
+    /// `height` is the visible height in world units, the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/// A look-at camera. Its view-projection ends up in `u_ViewProj` of the forward shaders.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    /// Width over height, kept in sync with the render target by [`crate::renderer::Renderer::resize`].
+    pub aspect: f32,
+    pub projection: Projection,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            eye: Point3::new(-6.0, 8.0, 12.0),
+            target: Point3::origin(),
+            up: Vector3::unit_y(),
+            aspect: 1.0,
+            projection: Projection::Perspective {
+                fovy: Deg(45.0),
+                near: 1.0,
+                far: 40.0,
+            },
+        }
+    }
+}
+
+impl Camera {
+    /// Blends position and target from `self` (alpha 0) to `other` (alpha 1); everything else is `other`'s.
+    pub fn lerp(&self, other: &Camera, alpha: f32) -> Camera {
+        Camera {
+            eye: self.eye + (other.eye - self.eye) * alpha,
+            target: self.target + (other.target - self.target) * alpha,
+            ..other.clone()
+        }
+    }
+
+    pub fn set_aspect(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective { fovy, near, far } => cgmath::perspective(fovy, self.aspect, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let (half_w, half_h) = (height * self.aspect / 2.0, height / 2.0);
+                cgmath::ortho(-half_w, half_w, -half_h, half_h, near, far)
+            }
+        }
+    }
+
+    /// Projection times view, already in wgpu clip space.
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        OPENGL_TO_WGPU_MATRIX * self.projection() * self.view()
+    }
+}
+
+/// Yaw and pitch of a direction, yaw measured from +X towards +Z. A zero vector (eye on the
+/// target) has no direction and gives (0, 0) instead of NaN.
+fn yaw_pitch(dir: Vector3<f32>) -> (Rad<f32>, Rad<f32>) {
+    if dir.magnitude2() <= f32::EPSILON {
+        return (Rad(0.0), Rad(0.0));
+    }
+    let dir = dir.normalize();
+    (Rad(dir.z.atan2(dir.x)), Rad(dir.y.clamp(-1.0, 1.0).asin()))
+}
+
+fn direction(yaw: Rad<f32>, pitch: Rad<f32>) -> Vector3<f32> {
+    Vector3::new(pitch.0.cos() * yaw.0.cos(), pitch.0.sin(), pitch.0.cos() * yaw.0.sin())
+}
+
+/// Tracks the cursor while a mouse button is held and hands out the accumulated movement.
+#[derive(Debug, Default, Clone)]
+struct Drag {
+    active: bool,
+    last: Option<PhysicalPosition<f64>>,
+    delta: (f32, f32),
+}
+
+impl Drag {
+    fn cursor_moved(&mut self, position: PhysicalPosition<f64>) -> bool {
+        if let (true, Some(last)) = (self.active, self.last) {
+            self.delta.0 += (position.x - last.x) as f32;
+            self.delta.1 += (position.y - last.y) as f32;
+        }
+        self.last = Some(position);
+        self.active
+    }
+
+    fn take(&mut self) -> (f32, f32) {
+        std::mem::take(&mut self.delta)
+    }
+}
+
+/// Circles around `target`: left drag rotates, the wheel zooms.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    /// Radians per pixel dragged.
+    pub rotate_speed: f32,
+    /// Fraction of the distance per wheel line.
+    pub zoom_speed: f32,
+    drag: Drag,
+    scroll: f32,
+}
+
+impl OrbitController {
+    /// Starts from wherever `camera` currently looks.
+    pub fn from_camera(camera: &Camera) -> Self {
+        let offset = camera.eye - camera.target;
+        let (yaw, pitch) = yaw_pitch(offset);
+        Self {
+            target: camera.target,
+            distance: offset.magnitude(),
+            yaw,
+            pitch,
+            rotate_speed: 0.005,
+            zoom_speed: 0.1,
+            drag: Drag::default(),
+            scroll: 0.0,
+        }
+    }
+
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.drag.active = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => self.drag.cursor_moved(*position),
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 50.0,
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, _dt: Duration) {
+        let (dx, dy) = self.drag.take();
+        self.yaw += Rad(dx * self.rotate_speed);
+        self.pitch = Rad((self.pitch.0 + dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH));
+        let scroll = std::mem::take(&mut self.scroll);
+        self.distance = (self.distance * (1.0 - scroll * self.zoom_speed)).max(0.1);
+
+        camera.target = self.target;
+        camera.eye = self.target + direction(self.yaw, self.pitch) * self.distance;
+    }
+}
+
+/// First-person movement: WASD to move, Space/Shift for up/down, right drag to look around.
+#[derive(Debug, Clone)]
+pub struct FlyController {
+    pub eye: Point3<f32>,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    /// World units per second.
+    pub speed: f32,
+    /// Radians per pixel dragged.
+    pub sensitivity: f32,
+    forward: f32,
+    right: f32,
+    up: f32,
+    pressed: [bool; 6],
+    drag: Drag,
+}
+
+impl FlyController {
+    pub fn from_camera(camera: &Camera) -> Self {
+        let (yaw, pitch) = yaw_pitch(camera.target - camera.eye);
+        Self {
+            eye: camera.eye,
+            yaw,
+            pitch,
+            speed: 5.0,
+            sensitivity: 0.003,
+            forward: 0.0,
+            right: 0.0,
+            up: 0.0,
+            pressed: [false; 6],
+            drag: Drag::default(),
+        }
+    }
+
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { event, .. } => {
+                let slot = match event.physical_key {
+                    PhysicalKey::Code(KeyCode::KeyW) => 0,
+                    PhysicalKey::Code(KeyCode::KeyS) => 1,
+                    PhysicalKey::Code(KeyCode::KeyD) => 2,
+                    PhysicalKey::Code(KeyCode::KeyA) => 3,
+                    PhysicalKey::Code(KeyCode::Space) => 4,
+                    PhysicalKey::Code(KeyCode::ShiftLeft) => 5,
+                    _ => return false,
+                };
+                self.pressed[slot] = event.state == ElementState::Pressed;
+                let axis = |pos: usize, neg: usize| self.pressed[pos] as i32 as f32 - self.pressed[neg] as i32 as f32;
+                (self.forward, self.right, self.up) = (axis(0, 1), axis(2, 3), axis(4, 5));
+                true
+            }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Right,
+                ..
+            } => {
+                self.drag.active = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => self.drag.cursor_moved(*position),
+            _ => false,
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let (dx, dy) = self.drag.take();
+        self.yaw += Rad(dx * self.sensitivity);
+        self.pitch = Rad((self.pitch.0 - dy * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH));
+
+        let forward = direction(self.yaw, self.pitch);
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let step = self.speed * dt.as_secs_f32();
+        self.eye += (forward * self.forward + right * self.right + Vector3::unit_y() * self.up) * step;
+
+        camera.eye = self.eye;
+        camera.target = self.eye + forward;
+        camera.up = Vector3::unit_y();
+    }
+}
+
+/// The active controller. Tab switches between the two, keeping the current view.
+#[derive(Debug, Clone)]
+pub enum CameraController {
+    Orbit(OrbitController),
+    Fly(FlyController),
+}
+
+impl CameraController {
+    pub fn process_event(&mut self, event: &WindowEvent) -> bool {
+        match self {
+            CameraController::Orbit(controller) => controller.process_event(event),
+            CameraController::Fly(controller) => controller.process_event(event),
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        match self {
+            CameraController::Orbit(controller) => controller.update_camera(camera, dt),
+            CameraController::Fly(controller) => controller.update_camera(camera, dt),
+        }
+    }
+
+    /// Swaps orbit for fly and back, starting the new one from `camera`.
+    pub fn toggle(&mut self, camera: &Camera) {
+        *self = match self {
+            CameraController::Orbit(_) => CameraController::Fly(FlyController::from_camera(camera)),
+            CameraController::Fly(_) => CameraController::Orbit(OrbitController::from_camera(camera)),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_finite(p: Point3<f32>) -> bool {
+        p.x.is_finite() && p.y.is_finite() && p.z.is_finite()
+    }
+
+    fn eye_on_target() -> Camera {
+        Camera {
+            eye: Point3::new(1.0, 2.0, 3.0),
+            target: Point3::new(1.0, 2.0, 3.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn yaw_pitch_round_trips() {
+        let (yaw, pitch) = yaw_pitch(Vector3::new(1.0, 1.0, 1.0));
+        let dir = direction(yaw, pitch);
+        assert!((dir - Vector3::new(1.0, 1.0, 1.0).normalize()).magnitude() < 1e-5);
+        assert_eq!(yaw_pitch(Vector3::new(0.0, 0.0, 0.0)), (Rad(0.0), Rad(0.0)));
+    }
+
+    #[test]
+    fn orbit_survives_eye_on_target() {
+        let mut camera = eye_on_target();
+        let mut orbit = OrbitController::from_camera(&camera);
+        orbit.update_camera(&mut camera, Duration::from_millis(16));
+        assert!(is_finite(camera.eye));
+        assert!(camera.eye != camera.target);
+    }
+
+    #[test]
+    fn fly_survives_eye_on_target() {
+        let mut camera = eye_on_target();
+        let mut fly = FlyController::from_camera(&camera);
+        fly.update_camera(&mut camera, Duration::from_millis(16));
+        assert!(is_finite(camera.eye) && is_finite(camera.target));
+        assert!(camera.eye != camera.target);
+    }
+}
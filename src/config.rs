@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use crate::game_loop::FrameRate;
+use crate::image::read_png;
+
+/// How the window surface and the frame loop are set up. Every field is a preference: anything the surface does
+/// not support falls back to the closest option it does, and the final choice is logged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    /// Tried in order, the first one the surface supports wins.
+    pub preferred_formats: Vec<wgpu::TextureFormat>,
+    /// Render through an sRGB view even when only a linear surface format is available.
+    pub srgb: bool,
+    /// `Fifo`, `Mailbox` or `Immediate`; unsupported modes fall back to `Fifo`.
+    pub present_mode: wgpu::PresentMode,
+    /// Frames the CPU may queue ahead of the GPU.
+    pub desired_maximum_frame_latency: u32,
+    pub alpha_mode: wgpu::CompositeAlphaMode,
+    /// Vsync-only, or a frame cap on top of the present mode.
+    pub frame_rate: FrameRate,
+    /// Fixed simulation steps per second, independent of the frame rate.
+    pub update_rate: f32,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            preferred_formats: vec![
+                wgpu::TextureFormat::Bgra8UnormSrgb,
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+                wgpu::TextureFormat::Bgra8Unorm,
+                wgpu::TextureFormat::Rgba8Unorm,
+            ],
+            srgb: true,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            frame_rate: FrameRate::Vsync,
+            update_rate: 60.0,
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: FrameRate) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    /// Picks the best supported surface setup. With `srgb` on a linear format, the sRGB
+    /// variant is added to `view_formats` and is what the renderer should draw in.
+    /// Fails when the surface reports no formats, i.e. the adapter cannot present to it.
+    pub fn surface_configuration(
+        &self,
+        capabilities: &wgpu::SurfaceCapabilities,
+        (width, height): (u32, u32),
+    ) -> Result<wgpu::SurfaceConfiguration, String> {
+        let format = self.pick_format(&capabilities.formats)?;
+        let view_formats = match format.add_srgb_suffix() {
+            srgb if self.srgb && srgb != format => vec![srgb],
+            _ => vec![],
+        };
+        let present_mode = self.pick_present_mode(&capabilities.present_modes);
+        let alpha_mode = match self.alpha_mode {
+            wgpu::CompositeAlphaMode::Auto => wgpu::CompositeAlphaMode::Auto,
+            mode if capabilities.alpha_modes.contains(&mode) => mode,
+            mode => {
+                let fallback = capabilities.alpha_modes.first().copied().unwrap_or(wgpu::CompositeAlphaMode::Auto);
+                println!("Alpha mode {mode:?} is not supported, using {fallback:?}");
+                fallback
+            }
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width,
+            height,
+            present_mode,
+            desired_maximum_frame_latency: self.desired_maximum_frame_latency.max(1),
+            alpha_mode,
+            view_formats,
+        };
+        println!(
+            "Surface: {:?} (drawing in {:?}), {:?}, frame latency {}, alpha {:?}",
+            config.format,
+            render_format(&config),
+            config.present_mode,
+            config.desired_maximum_frame_latency,
+            config.alpha_mode
+        );
+        Ok(config)
+    }
+
+    fn pick_format(&self, supported: &[wgpu::TextureFormat]) -> Result<wgpu::TextureFormat, String> {
+        if let Some(format) = self.preferred_formats.iter().find(|format| supported.contains(format)) {
+            return Ok(*format);
+        }
+        // 没有首选格式时,优先选 sRGB 属性一致的格式
+        let fallback = supported
+            .iter()
+            .find(|format| format.is_srgb() == self.srgb)
+            .or(supported.first())
+            .copied()
+            .ok_or("the surface supports no formats, the adapter cannot present to this window")?;
+        println!("None of {:?} is supported, falling back to {fallback:?}", self.preferred_formats);
+        Ok(fallback)
+    }
+
+    fn pick_present_mode(&self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        match self.present_mode {
+            // wgpu resolves these to whatever the surface has
+            mode @ (wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync) => mode,
+            mode if supported.contains(&mode) => mode,
+            mode => {
+                println!("Present mode {mode:?} is not supported, using Fifo");
+                wgpu::PresentMode::Fifo
+            }
+        }
+    }
+}
+
+/// The format frames are drawn in: the sRGB view format when there is one, else the surface format.
+pub fn render_format(config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+    config.view_formats.first().copied().unwrap_or(config.format)
+}
+
+/// What Alt+Enter switches to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    /// A borderless window covering the current monitor.
+    #[default]
+    Borderless,
+    /// The current monitor's largest video mode, falling back to borderless when it lists none.
+    Exclusive,
+}
+
+/// How the window is opened. A placement remembered in `settings_file` wins over `size`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    /// Inner size in logical pixels.
+    pub size: (u32, u32),
+    /// Smallest inner size in logical pixels, `None` for no limit.
+    pub min_size: Option<(u32, u32)>,
+    pub resizable: bool,
+    pub fullscreen: FullscreenMode,
+    /// Open in `fullscreen` instead of a window.
+    pub start_fullscreen: bool,
+    /// PNG for the title bar and task bar.
+    pub icon: Option<PathBuf>,
+    /// Where the last position and size are kept between runs, `None` to always open at `size`.
+    pub settings_file: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Hello Wgpu!".to_string(),
+            size: (1024, 768),
+            min_size: None,
+            resizable: true,
+            fullscreen: FullscreenMode::default(),
+            start_fullscreen: false,
+            icon: None,
+            settings_file: None,
+        }
+    }
+}
+
+/// Window placement remembered between runs, in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSettings {
+    /// Outer position; some platforms (Wayland) never report one.
+    pub position: Option<(i32, i32)>,
+    /// Inner size.
+    pub size: (u32, u32),
+}
+
+impl WindowSettings {
+    /// `None` when the file is missing or broken, which only costs the remembered placement.
+    pub fn load(path: &Path) -> Option<Self> {
+        let text = std::fs::read_to_string(path).ok()?;
+        let (mut x, mut y, mut width, mut height) = (None, None, None, None);
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "x" => x = value.parse().ok(),
+                "y" => y = value.parse().ok(),
+                "width" => width = value.parse().ok(),
+                "height" => height = value.parse().ok(),
+                _ => {}
+            }
+        }
+        Some(Self {
+            position: x.zip(y),
+            size: (width?, height?),
+        })
+        .filter(|settings| settings.size.0 > 0 && settings.size.1 > 0)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut text = format!("width = {}\nheight = {}\n", self.size.0, self.size.1);
+        if let Some((x, y)) = self.position {
+            text += &format!("x = {x}\ny = {y}\n");
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text)
+    }
+}
+
+/// Reads a PNG (8-bit RGB or RGBA) into a window icon.
+pub fn load_icon(path: &Path) -> Result<winit::window::Icon, String> {
+    let image = read_png(path).map_err(|e| e.to_string())?;
+    winit::window::Icon::from_rgba(image.pixels, image.width, image.height)
+        .map_err(|e| format!("bad icon {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(formats: &[wgpu::TextureFormat]) -> wgpu::SurfaceCapabilities {
+        wgpu::SurfaceCapabilities {
+            formats: formats.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_formats_is_an_error() {
+        let config = AppConfig::default();
+        assert!(config.surface_configuration(&capabilities(&[]), (1, 1)).is_err());
+    }
+
+    #[test]
+    fn linear_fallback_draws_in_srgb() {
+        let config = AppConfig::default();
+        let surface = config.surface_configuration(&capabilities(&[wgpu::TextureFormat::Rgba16Float]), (1, 1)).unwrap();
+        assert_eq!(surface.format, wgpu::TextureFormat::Rgba16Float);
+
+        let surface = config.surface_configuration(&capabilities(&[wgpu::TextureFormat::Bgra8Unorm]), (1, 1)).unwrap();
+        assert_eq!(surface.format, wgpu::TextureFormat::Bgra8Unorm);
+        assert_eq!(render_format(&surface), wgpu::TextureFormat::Bgra8UnormSrgb);
+    }
+}
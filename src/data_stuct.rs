@@ -1,19 +1,26 @@
+use std::collections::HashMap;
 use std::ops::Range;
 use std::rc::Rc;
 use wgpu::Buffer;
 
-#[allow(dead_code)]
+use crate::depth::DepthBuffer;
+use crate::reflection::PipelineReflection;
+use crate::scene::{EntityId, LightId, MeshKind};
+
 #[derive(Debug)]
-struct CubeDesc {
-    offset: cgmath::Vector3<f32>,
-    angle: f32,
-    scale: f32,
-    rotation: f32,
+pub struct CubeDesc {
+    pub offset: cgmath::Vector3<f32>,
+    pub angle: f32,
+    pub scale: f32,
+    pub rotation: f32,
 }
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Entity {
+    pub id: EntityId,
+    pub mesh: MeshKind,
     pub mx_world: cgmath::Matrix4<f32>,
+    /// `mx_world` before the last update step, drawn frames blend between the two.
+    pub prev_world: cgmath::Matrix4<f32>,
     pub rotation_speed: f32,
     pub color: wgpu::Color,
     pub vertex_buf: Rc<wgpu::Buffer>,
@@ -21,35 +28,58 @@ pub struct Entity {
     pub index_count: usize,
     pub bind_group: wgpu::BindGroup,
     pub uniform_buf: wgpu::Buffer,
+    /// Set when `mx_world` or `color` changed since the last upload.
+    pub uniform_dirty: bool,
+}
+/// GPU buffers of one mesh, shared between all entities that draw it.
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    pub vertex_buf: Rc<wgpu::Buffer>,
+    pub index_buf: Rc<wgpu::Buffer>,
+    pub index_count: usize,
 }
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Light {
+    pub id: LightId,
     pub pos: cgmath::Point3<f32>,
     pub color: wgpu::Color,
     pub fov: f32,
     pub depth: Range<f32>,
     pub target_view: wgpu::TextureView,
 }
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct Pass {
     pub pipeline: wgpu::RenderPipeline,
-    // pub bind_group: wgpu::BindGroup,
-    // pub uniform_buf: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+    pub uniform_buf: wgpu::Buffer,
+    /// Bindings the pipeline was built with. A shader reload may only swap the pipeline
+    /// while these stay the same, otherwise the existing bind groups no longer fit.
+    pub reflection: PipelineReflection,
+}
+
+/// A pipeline drawing its own geometry without any bindings, like the pentagon demo.
+#[derive(Debug)]
+pub struct MeshPass {
+    pub pipeline: wgpu::RenderPipeline,
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
     pub index_count: u32,
 }
 
-#[allow(dead_code)]
 #[derive(Debug)]
 pub struct State {
-    // light:Vec<Light>,
-    // lights_are_dirty: bool,
-    // light_uniform_buf: wgpu::Buffer,
-    // entity: Vec<Entity>,
-    // shadow_pass: Pass,
-    // forward_depth: wgpu::TextureView,
+    pub lights: Vec<Light>,
+    pub lights_are_dirty: bool,
+    pub light_uniform_buf: wgpu::Buffer,
+    pub next_light_id: u32,
+    /// One layer per light slot, the light at index `i` renders into layer `i`.
+    pub shadow_texture: wgpu::Texture,
+    pub entities: Vec<Entity>,
+    pub next_entity_id: u32,
+    pub meshes: HashMap<MeshKind, Mesh>,
+    pub entity_bind_group_layout: wgpu::BindGroupLayout,
+    pub shadow_pass: Pass,
+    /// `None` when the renderer has depth testing turned off.
+    pub forward_depth: Option<DepthBuffer>,
     pub forward_pass: Pass,
-}
\ No newline at end of file
+}
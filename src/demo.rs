@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use winit::event::WindowEvent;
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use crate::app::{App, AppContext};
+use crate::camera::{Camera, CameraController, OrbitController};
+
+/// The built-in demo: draws whichever scene the application was built with and moves the
+/// camera with the orbit controller, Tab switching to the fly one.
+#[derive(Debug)]
+pub struct DemoApp {
+    camera_controller: CameraController,
+}
+
+impl Default for DemoApp {
+    fn default() -> Self {
+        Self {
+            camera_controller: CameraController::Orbit(OrbitController::from_camera(&Camera::default())),
+        }
+    }
+}
+
+impl App for DemoApp {
+    fn init(&mut self, ctx: &mut AppContext) {
+        self.camera_controller = CameraController::Orbit(OrbitController::from_camera(ctx.renderer.camera()));
+    }
+
+    fn update(&mut self, ctx: &mut AppContext, dt: Duration) {
+        self.camera_controller.update_camera(ctx.renderer.camera_mut(), dt);
+    }
+
+    fn on_event(&mut self, ctx: &mut AppContext, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { event: key, .. }
+                if key.state.is_pressed() && key.physical_key == PhysicalKey::Code(KeyCode::Tab) =>
+            {
+                self.camera_controller.toggle(ctx.renderer.camera());
+            }
+            event => {
+                self.camera_controller.process_event(event);
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/// Depth testing for a pipeline, and the format of the depth texture it renders into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthTest {
+    pub format: wgpu::TextureFormat,
+    pub compare: wgpu::CompareFunction,
+    pub write_enabled: bool,
+}
+
+impl Default for DepthTest {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Depth32Float,
+            compare: wgpu::CompareFunction::Less,
+            write_enabled: true,
+        }
+    }
+}
+
+impl DepthTest {
+    /// For `RenderPipelineDescriptor::depth_stencil`.
+    pub fn state(&self) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: self.format,
+            depth_write_enabled: self.write_enabled,
+            depth_compare: self.compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+}
+
+/// A depth texture that follows the size of the color target.
+#[derive(Debug)]
+pub struct DepthBuffer {
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    size: (u32, u32),
+    view: wgpu::TextureView,
+}
+
+impl DepthBuffer {
+    /// `sample_count` has to match the color target it is used with.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, size: (u32, u32)) -> Self {
+        assert!(format.is_depth_stencil_format(), "{format:?} is not a depth format");
+        Self {
+            format,
+            sample_count,
+            size,
+            view: create_view(device, format, sample_count, size),
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Recreates the texture if the size changed.
+    pub fn resize(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if size != self.size && size.0 > 0 && size.1 > 0 {
+            *self = Self::new(device, self.format, self.sample_count, size);
+        }
+    }
+
+    /// Cleared to the far plane and thrown away after the pass.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: self.format.has_stencil_aspect().then_some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Discard,
+            }),
+        }
+    }
+}
+
+fn create_view(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    sample_count: u32,
+    (width, height): (u32, u32),
+) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some("Depth Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
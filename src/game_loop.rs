@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+/// Most update steps run for one frame. After a long stall (a breakpoint, dragging the window)
+/// the rest of the backlog is dropped instead of freezing while the simulation catches up.
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+/// How often frames are drawn. The simulation runs at its own fixed rate either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameRate {
+    /// Draw whenever the previous frame was presented, paced by the present mode.
+    Vsync,
+    /// Draw at most this many frames per second, sleeping in between.
+    Target(f32),
+}
+
+/// Accumulates frame time and hands it out in fixed `step`s, so the simulation gives the same
+/// result at any frame rate.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    last_tick: Instant,
+}
+
+impl FixedTimestep {
+    pub fn new(updates_per_second: f32) -> Self {
+        Self {
+            step: Duration::from_secs_f32(1.0 / updates_per_second.max(1.0)),
+            accumulator: Duration::ZERO,
+            last_tick: Instant::now(),
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds the time since the last call and returns how many update steps are due.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        self.accumulator += now.saturating_duration_since(self.last_tick);
+        self.last_tick = now;
+        let steps = (self.accumulator.as_nanos() / self.step.as_nanos()) as u32;
+        self.accumulator -= self.step * steps;
+        if steps > MAX_STEPS_PER_FRAME {
+            self.accumulator = Duration::ZERO;
+            return MAX_STEPS_PER_FRAME;
+        }
+        steps
+    }
+
+    /// How far the time left over after the due steps goes towards the next one, in `0..1`.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+
+    /// When the next step becomes due.
+    pub fn next_step(&self) -> Instant {
+        self.last_tick + (self.step - self.accumulator)
+    }
+}
+
+/// Decides when the next frame should be drawn for a [`FrameRate`].
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    frame_rate: FrameRate,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: FrameRate) -> Self {
+        Self {
+            frame_rate,
+            next_frame: Instant::now(),
+        }
+    }
+
+    pub fn frame_rate(&self) -> FrameRate {
+        self.frame_rate
+    }
+
+    /// `None` means draw right away and let presenting block.
+    pub fn next_frame(&self) -> Option<Instant> {
+        match self.frame_rate {
+            FrameRate::Vsync => None,
+            FrameRate::Target(_) => Some(self.next_frame),
+        }
+    }
+
+    pub fn frame_due(&self, now: Instant) -> bool {
+        self.next_frame().is_none_or(|next| now >= next)
+    }
+
+    /// Schedules the frame after the one drawn at `now`.
+    pub fn frame_drawn(&mut self, now: Instant) {
+        if let FrameRate::Target(fps) = self.frame_rate {
+            let interval = Duration::from_secs_f32(1.0 / fps.max(1.0));
+            // 落后太多时从现在重新计时,而不是连续补帧
+            self.next_frame = match self.next_frame + interval {
+                next if next < now => now + interval,
+                next => next,
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// 50 updates per second, started at `start`.
+    fn timestep(start: Instant) -> FixedTimestep {
+        FixedTimestep {
+            step: 20 * MS,
+            accumulator: Duration::ZERO,
+            last_tick: start,
+        }
+    }
+
+    #[test]
+    fn counts_due_steps_and_keeps_the_remainder() {
+        let start = Instant::now();
+        let mut timestep = timestep(start);
+        assert_eq!(timestep.advance(start + 10 * MS), 0);
+        assert_eq!(timestep.advance(start + 50 * MS), 2);
+        assert_eq!(timestep.accumulator, 10 * MS);
+        assert_eq!(timestep.advance(start + 60 * MS), 1);
+        assert_eq!(timestep.accumulator, Duration::ZERO);
+        assert_eq!(timestep.next_step(), start + 80 * MS);
+    }
+
+    #[test]
+    fn caps_catch_up_and_drops_the_backlog() {
+        let start = Instant::now();
+        let mut timestep = timestep(start);
+        assert_eq!(timestep.advance(start + 10 * 1000 * MS), MAX_STEPS_PER_FRAME);
+        assert_eq!(timestep.alpha(), 0.0);
+        // 丢掉积压后按正常节奏继续
+        assert_eq!(timestep.advance(start + 10_020 * MS), 1);
+    }
+
+    #[test]
+    fn time_going_backwards_is_ignored() {
+        let start = Instant::now() + 100 * MS;
+        let mut timestep = timestep(start);
+        assert_eq!(timestep.advance(start - 50 * MS), 0);
+        assert_eq!(timestep.accumulator, Duration::ZERO);
+    }
+
+    #[test]
+    fn alpha_stays_in_unit_range() {
+        let start = Instant::now();
+        let mut timestep = timestep(start);
+        for ms in (0..200).step_by(7) {
+            timestep.advance(start + ms * MS);
+            let alpha = timestep.alpha();
+            assert!((0.0..1.0).contains(&alpha), "alpha {alpha} at {ms} ms");
+        }
+        timestep.advance(start + 215 * MS);
+        assert!((timestep.alpha() - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn update_rate_is_clamped() {
+        assert_eq!(FixedTimestep::new(0.0).step(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn vsync_draws_whenever_asked() {
+        let mut pacer = FramePacer::new(FrameRate::Vsync);
+        let now = Instant::now();
+        assert_eq!(pacer.next_frame(), None);
+        pacer.frame_drawn(now);
+        assert!(pacer.frame_due(now));
+    }
+
+    #[test]
+    fn target_rate_schedules_frames_at_the_interval() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(FrameRate::Target(100.0));
+        pacer.next_frame = start;
+        assert!(pacer.frame_due(start));
+
+        pacer.frame_drawn(start);
+        let next = pacer.next_frame().unwrap();
+        assert!(next > start + 9 * MS && next < start + 11 * MS);
+        assert!(!pacer.frame_due(start + 5 * MS));
+        assert!(pacer.frame_due(next));
+
+        // 稍晚画完时下一帧仍按原计划,不把延迟累积下去
+        pacer.frame_drawn(next + MS);
+        assert_eq!(pacer.next_frame(), Some(next + (next - start)));
+    }
+
+    #[test]
+    fn falling_behind_restarts_the_schedule() {
+        let start = Instant::now();
+        let mut pacer = FramePacer::new(FrameRate::Target(100.0));
+        pacer.next_frame = start;
+        let late = start + 500 * MS;
+        pacer.frame_drawn(late);
+        let next = pacer.next_frame().unwrap();
+        assert!(next > late && next < late + 11 * MS);
+    }
+}
@@ -0,0 +1,334 @@
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use crate::headless::{HeadlessError, HeadlessOptions, HeadlessRenderer, RenderedImage};
+use crate::image::{read_png, write_png, ImageError};
+pub use crate::renderer::Scene;
+
+/// Set to `1` to overwrite the references with the current output instead of comparing.
+pub const UPDATE_ENV: &str = "GOLDEN_UPDATE";
+
+/// Renders `scene` offscreen on whatever adapter is available.
+pub async fn render_scene(scene: Scene, width: u32, height: u32) -> Result<RenderedImage, HeadlessError> {
+    let mut headless = HeadlessRenderer::new(HeadlessOptions {
+        width,
+        height,
+        scene,
+        ..Default::default()
+    })
+    .await?;
+    headless.render()
+}
+
+/// When two images count as the same. Software and hardware rasterizers disagree slightly
+/// on edges, so a bit of slack is needed for references to pass on both.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompareOptions {
+    /// Largest per-channel difference that is ignored outright.
+    pub channel_tolerance: u8,
+    /// Perceptual (YIQ) distance in 0..=1 below which a pixel still matches.
+    pub perceptual_threshold: f32,
+    /// Fraction of pixels allowed to mismatch before the comparison fails.
+    pub max_mismatch_ratio: f32,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            channel_tolerance: 2,
+            perceptual_threshold: 0.1,
+            max_mismatch_ratio: 0.001,
+        }
+    }
+}
+
+/// Outcome of comparing two images of the same size.
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    pub mismatched: usize,
+    pub total: usize,
+    /// Largest perceptual distance seen, 0..=1.
+    pub max_distance: f32,
+    /// The reference faded to gray with mismatched pixels in red.
+    pub diff: RenderedImage,
+}
+
+impl Comparison {
+    pub fn passes(&self, options: &CompareOptions) -> bool {
+        self.mismatched as f32 <= self.total as f32 * options.max_mismatch_ratio
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoldenError {
+    #[error(transparent)]
+    Image(#[from] ImageError),
+    #[error("cannot compare {format:?} images, only RGBA8 and BGRA8")]
+    UnsupportedFormat { format: wgpu::TextureFormat },
+    #[error("no reference image at {path}, run with {UPDATE_ENV}=1 to create it")]
+    MissingReference { path: PathBuf },
+    #[error("cannot compare a {actual:?} image with a {reference:?} reference")]
+    SizeMismatch { actual: (u32, u32), reference: (u32, u32) },
+    #[error("{name}: rendered {actual:?} but the reference is {reference:?}, see {actual_path}")]
+    WrongSize {
+        name: String,
+        actual: (u32, u32),
+        reference: (u32, u32),
+        actual_path: PathBuf,
+    },
+    #[error(
+        "{name}: {mismatched} of {total} pixels differ (max distance {max_distance:.3}), \
+         see {actual_path} and {diff_path}"
+    )]
+    Mismatch {
+        name: String,
+        mismatched: usize,
+        total: usize,
+        max_distance: f32,
+        actual_path: PathBuf,
+        diff_path: PathBuf,
+    },
+}
+
+/// Compares rendered frames with checked-in PNG references.
+#[derive(Debug, Clone)]
+pub struct Golden {
+    pub reference_dir: PathBuf,
+    /// Where actual and diff images go when a comparison fails.
+    pub output_dir: PathBuf,
+    pub options: CompareOptions,
+    /// Write references instead of comparing. Defaults to the `GOLDEN_UPDATE` variable.
+    pub update: bool,
+}
+
+impl Golden {
+    pub fn new(reference_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            reference_dir: reference_dir.into(),
+            output_dir: PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden"),
+            options: CompareOptions::default(),
+            update: std::env::var(UPDATE_ENV).is_ok_and(|v| v == "1"),
+        }
+    }
+
+    pub fn with_options(mut self, options: CompareOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Checks `image` against `<reference_dir>/<name>.png`, or overwrites it in update mode.
+    pub fn check(&self, name: &str, image: &RenderedImage) -> Result<(), GoldenError> {
+        let reference_path = self.reference_dir.join(format!("{name}.png"));
+        if self.update {
+            println!("updating {}", reference_path.display());
+            return Ok(write_png(&reference_path, image)?);
+        }
+        if !reference_path.exists() {
+            return Err(GoldenError::MissingReference { path: reference_path });
+        }
+
+        let reference = read_png(&reference_path)?;
+        let actual_path = self.output_dir.join(format!("{name}.actual.png"));
+        let comparison = match compare(&reference, image, &self.options) {
+            Err(GoldenError::SizeMismatch { actual, reference }) => {
+                write_png(&actual_path, image)?;
+                return Err(GoldenError::WrongSize {
+                    name: name.to_string(),
+                    actual,
+                    reference,
+                    actual_path,
+                });
+            }
+            result => result?,
+        };
+        if comparison.passes(&self.options) {
+            return Ok(());
+        }
+        let diff_path = self.output_dir.join(format!("{name}.diff.png"));
+        write_png(&actual_path, image)?;
+        write_png(&diff_path, &comparison.diff)?;
+        Err(GoldenError::Mismatch {
+            name: name.to_string(),
+            mismatched: comparison.mismatched,
+            total: comparison.total,
+            max_distance: comparison.max_distance,
+            actual_path,
+            diff_path,
+        })
+    }
+}
+
+/// Pixel-by-pixel comparison of two images of the same size. Both are converted to RGBA8
+/// first, so a BGRA frame compares equal to its RGBA reference.
+pub fn compare(
+    reference: &RenderedImage,
+    actual: &RenderedImage,
+    options: &CompareOptions,
+) -> Result<Comparison, GoldenError> {
+    if (reference.width, reference.height) != (actual.width, actual.height) {
+        return Err(GoldenError::SizeMismatch {
+            actual: (actual.width, actual.height),
+            reference: (reference.width, reference.height),
+        });
+    }
+    let (reference_pixels, actual_pixels) = (rgba8(reference)?, rgba8(actual)?);
+    let mut mismatched = 0;
+    let mut max_distance = 0f32;
+    let mut diff = Vec::with_capacity(reference_pixels.len());
+    for (a, b) in reference_pixels.chunks_exact(4).zip(actual_pixels.chunks_exact(4)) {
+        let within_tolerance = a.iter().zip(b).all(|(a, b)| a.abs_diff(*b) <= options.channel_tolerance);
+        let distance = if within_tolerance { 0.0 } else { perceptual_distance(a, b) };
+        max_distance = max_distance.max(distance);
+        if distance > options.perceptual_threshold {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = 255 - ((255 - luma(a) as u32) / 4) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    Ok(Comparison {
+        mismatched,
+        total: reference_pixels.len() / 4,
+        max_distance,
+        diff: RenderedImage {
+            width: reference.width,
+            height: reference.height,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            pixels: diff,
+        },
+    })
+}
+
+fn rgba8(image: &RenderedImage) -> Result<Cow<'_, [u8]>, GoldenError> {
+    image.to_rgba8().ok_or(GoldenError::UnsupportedFormat { format: image.format })
+}
+
+fn luma(p: &[u8]) -> u8 {
+    (0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32) as u8
+}
+
+/// Weighted YIQ distance (as used by pixelmatch), alpha-blended over white, scaled to 0..=1.
+fn perceptual_distance(a: &[u8], b: &[u8]) -> f32 {
+    const MAX_DELTA: f32 = 35215.0;
+    let blend = |p: &[u8], i: usize| 255.0 + (p[i] as f32 - 255.0) * (p[3] as f32 / 255.0);
+    let yiq = |p: &[u8]| {
+        let (r, g, b) = (blend(p, 0), blend(p, 1), blend(p, 2));
+        (
+            r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_2,
+            r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9,
+            r * 0.211_470_2 - g * 0.522_617_2 + b * 0.311_147,
+        )
+    };
+    let (ya, ia, qa) = yiq(a);
+    let (yb, ib, qb) = yiq(b);
+    let (y, i, q) = (ya - yb, ia - ib, qa - qb);
+    ((0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_DELTA).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(format: wgpu::TextureFormat, pixels: &[[u8; 4]]) -> RenderedImage {
+        RenderedImage {
+            width: pixels.len() as u32,
+            height: 1,
+            format,
+            pixels: pixels.concat(),
+        }
+    }
+
+    fn rgba(pixels: &[[u8; 4]]) -> RenderedImage {
+        image(wgpu::TextureFormat::Rgba8UnormSrgb, pixels)
+    }
+
+    #[test]
+    fn identical_images_match() {
+        let a = rgba(&[[10, 20, 30, 255], [200, 100, 50, 255]]);
+        let comparison = compare(&a, &a, &CompareOptions::default()).unwrap();
+        assert_eq!((comparison.mismatched, comparison.total), (0, 2));
+        assert_eq!(comparison.max_distance, 0.0);
+        assert!(comparison.passes(&CompareOptions::default()));
+    }
+
+    #[test]
+    fn small_differences_are_within_tolerance() {
+        let options = CompareOptions::default();
+        let reference = rgba(&[[100, 100, 100, 255], [50, 60, 70, 255]]);
+        // 第一个像素在逐通道容差内,第二个超出容差但感知距离很小
+        let actual = rgba(&[[102, 98, 100, 255], [55, 60, 70, 255]]);
+        let comparison = compare(&reference, &actual, &options).unwrap();
+        assert_eq!(comparison.mismatched, 0);
+        assert!(comparison.max_distance > 0.0 && comparison.max_distance <= options.perceptual_threshold);
+    }
+
+    #[test]
+    fn different_pixels_mismatch() {
+        let options = CompareOptions::default();
+        let reference = rgba(&[[0, 0, 0, 255], [255, 255, 255, 255]]);
+        let actual = rgba(&[[255, 255, 255, 255], [255, 255, 255, 255]]);
+        let comparison = compare(&reference, &actual, &options).unwrap();
+        assert_eq!(comparison.mismatched, 1);
+        assert!(!comparison.passes(&options));
+        assert_eq!(comparison.diff.pixel(0, 0), [255, 0, 0, 255]);
+        assert_ne!(comparison.diff.pixel(1, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bgra_is_compared_as_rgba() {
+        let reference = rgba(&[[255, 0, 0, 255]]);
+        let actual = image(wgpu::TextureFormat::Bgra8UnormSrgb, &[[0, 0, 255, 255]]);
+        let comparison = compare(&reference, &actual, &CompareOptions::default()).unwrap();
+        assert_eq!(comparison.mismatched, 0);
+    }
+
+    #[test]
+    fn other_formats_are_rejected() {
+        let reference = rgba(&[[0, 0, 0, 255]]);
+        let actual = image(wgpu::TextureFormat::Rgba8Snorm, &[[0, 0, 0, 255]]);
+        let result = compare(&reference, &actual, &CompareOptions::default());
+        assert!(matches!(result, Err(GoldenError::UnsupportedFormat { .. })));
+    }
+
+    #[test]
+    fn different_sizes_are_an_error() {
+        let reference = rgba(&[[0, 0, 0, 255], [0, 0, 0, 255]]);
+        let actual = rgba(&[[0, 0, 0, 255]]);
+        let result = compare(&reference, &actual, &CompareOptions::default());
+        assert!(matches!(
+            result,
+            Err(GoldenError::SizeMismatch {
+                actual: (1, 1),
+                reference: (2, 1)
+            })
+        ));
+    }
+
+    #[test]
+    fn check_reports_the_size_mismatch() {
+        let dir = std::env::temp_dir().join(format!("golden-size-{}", std::process::id()));
+        let mut golden = Golden::new(&dir);
+        golden.output_dir = dir.join("out");
+        golden.update = false;
+        write_png(&dir.join("size.png"), &rgba(&[[0, 0, 0, 255], [0, 0, 0, 255]])).unwrap();
+
+        let result = golden.check("size", &rgba(&[[0, 0, 0, 255]]));
+        let Err(GoldenError::WrongSize { actual_path, .. }) = result else {
+            panic!("expected WrongSize, got {result:?}");
+        };
+        assert!(actual_path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn perceptual_distance_range() {
+        let (black, white) = ([0, 0, 0, 255], [255, 255, 255, 255]);
+        assert_eq!(perceptual_distance(&black, &black), 0.0);
+        assert!(perceptual_distance(&black, &white) > 0.9);
+        assert!(perceptual_distance(&black, &white) <= 1.0);
+        // 全透明的像素混合到白色上,和白色没有区别
+        assert_eq!(perceptual_distance(&[0, 0, 0, 0], &white), 0.0);
+        assert!(perceptual_distance(&[100, 100, 100, 255], &[110, 100, 100, 255]) < 0.1);
+    }
+}
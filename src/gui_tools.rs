@@ -1,13 +1,27 @@
 use egui::*;
 use egui_wgpu::{Renderer, ScreenDescriptor};
 use egui_winit::State;
-use wgpu::{CommandEncoder, Device, Queue, StoreOp, TextureFormat, TextureView};
+use wgpu::{CommandEncoder, Device, Queue, TextureFormat};
 use winit::event::WindowEvent;
 use winit::window::Window;
 
+use crate::msaa::ColorTarget;
+
 pub struct GuiRenderer {
     state: State,
     renderer: Renderer,
+    output_color_format: TextureFormat,
+    output_depth_format: Option<TextureFormat>,
+    msaa_samples: u32,
+}
+
+impl std::fmt::Debug for GuiRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GuiRenderer")
+            .field("output_color_format", &self.output_color_format)
+            .field("msaa_samples", &self.msaa_samples)
+            .finish_non_exhaustive()
+    }
 }
 
 impl GuiRenderer {
@@ -41,24 +55,45 @@ impl GuiRenderer {
         GuiRenderer {
             state: egui_state,
             renderer: gui_renderer,
+            output_color_format,
+            output_depth_format,
+            msaa_samples,
+        }
+    }
+
+    /// Must equal the scene renderer's sample count.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    /// Rebuilds the egui pipeline for a new sample count.
+    pub fn set_msaa_samples(&mut self, device: &Device, window: &Window, msaa_samples: u32) {
+        if msaa_samples == self.msaa_samples {
+            return;
         }
+        // 新的 Renderer 没有字体纹理,只有新的 Context 才会重新上传,所以整个重建,保留窗口位置等状态
+        let memory = self.context().memory(|memory| memory.clone());
+        *self = Self::new(device, self.output_color_format, self.output_depth_format, msaa_samples, window);
+        self.context().memory_mut(|m| *m = memory);
     }
 
-    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) {
-        let _ = self.state.on_window_event(window, &event);
+    /// Returns true when egui used the event, e.g. a click on a panel, and nothing else should see it.
+    pub fn handle_input(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        self.state.on_window_event(window, event).consumed
     }
 
     pub fn ppp(&mut self, v: f32) {
         self.state.egui_ctx().set_pixels_per_point(v);
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &mut self,
         device: &Device,
         queue: &Queue,
         encoder: &mut CommandEncoder,
         window: &Window,
-        window_surface_view: &TextureView,
+        target: ColorTarget,
         screen_descriptor: ScreenDescriptor,
         run_ui: impl FnOnce(&Context),
     ) {
@@ -66,13 +101,13 @@ impl GuiRenderer {
             .egui_ctx()
             .set_pixels_per_point(screen_descriptor.pixels_per_point);
 
-        let raw_input = self.state.take_egui_input(&window);
+        let raw_input = self.state.take_egui_input(window);
         let full_output = self.state.egui_ctx().run(raw_input, |_ui| {
-            run_ui(&self.state.egui_ctx());
+            run_ui(self.state.egui_ctx());
         });
 
         self.state
-            .handle_platform_output(&window, full_output.platform_output);
+            .handle_platform_output(window, full_output.platform_output);
 
         let tris = self
             .state
@@ -80,19 +115,12 @@ impl GuiRenderer {
             .tessellate(full_output.shapes, self.state.egui_ctx().pixels_per_point());
         for (id, image_delta) in &full_output.textures_delta.set {
             self.renderer
-                .update_texture(&device, &queue, *id, &image_delta);
+                .update_texture(device, queue, *id, image_delta);
         }
         self.renderer
-            .update_buffers(&device, &queue, encoder, &tris, &screen_descriptor);
+            .update_buffers(device, queue, encoder, &tris, &screen_descriptor);
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &window_surface_view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: StoreOp::Store,
-                },
-            })],
+            color_attachments: &[Some(target.attachment(wgpu::LoadOp::Load))],
             depth_stencil_attachment: None,
             timestamp_writes: None,
             label: Some("egui main render pass"),
@@ -0,0 +1,265 @@
+use std::borrow::Cow;
+use std::sync::mpsc::channel;
+
+use crate::renderer::{AssetMode, Renderer, Scene};
+
+/// How to set up a renderer that draws into an offscreen texture instead of a window.
+#[derive(Debug, Clone)]
+pub struct HeadlessOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Format of the offscreen color target. Readback needs a 4-byte-per-pixel format.
+    pub format: wgpu::TextureFormat,
+    pub backends: wgpu::Backends,
+    /// Go straight to a software adapter (llvmpipe, WARP, ...) instead of trying hardware first.
+    pub force_fallback_adapter: bool,
+    pub asset_mode: AssetMode,
+    pub scene: Scene,
+    /// MSAA samples, resolved into the target before readback.
+    pub sample_count: u32,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 768,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            backends: wgpu::Backends::all(),
+            force_fallback_adapter: false,
+            asset_mode: AssetMode::Embedded,
+            scene: Scene::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HeadlessError {
+    #[error("no graphics adapter found, not even a software fallback")]
+    NoAdapter,
+    #[error("failed to create device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+    #[error("{0:?} cannot be read back, use a format with 4 bytes per pixel")]
+    UnsupportedFormat(wgpu::TextureFormat),
+    #[error("{0}")]
+    SampleCount(String),
+    #[error("shader error:\n{0}")]
+    Shader(String),
+    #[error("failed to read the frame back: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+}
+
+/// A frame copied back to CPU memory, rows tightly packed top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub pixels: Vec<u8>,
+}
+
+impl RenderedImage {
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * self.width + x) * 4) as usize;
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    /// The pixels as RGBA8, swizzling BGRA frames. `None` for any other format.
+    pub fn to_rgba8(&self) -> Option<Cow<'_, [u8]>> {
+        use wgpu::TextureFormat::*;
+        match self.format {
+            Rgba8Unorm | Rgba8UnormSrgb => Some(Cow::Borrowed(&self.pixels)),
+            Bgra8Unorm | Bgra8UnormSrgb => {
+                Some(Cow::Owned(self.pixels.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Renders the same passes as the windowed app into an offscreen texture, for CI machines
+/// with no display and possibly no GPU.
+#[derive(Debug)]
+pub struct HeadlessRenderer {
+    renderer: Renderer,
+    adapter_info: wgpu::AdapterInfo,
+    target: wgpu::Texture,
+    readback: wgpu::Buffer,
+    padded_bytes_per_row: u32,
+}
+
+impl HeadlessRenderer {
+    pub async fn new(options: HeadlessOptions) -> Result<Self, HeadlessError> {
+        if options.format.block_copy_size(None) != Some(4) {
+            return Err(HeadlessError::UnsupportedFormat(options.format));
+        }
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backends,
+            ..Default::default()
+        });
+        let adapter = request_any_adapter(&instance, &options).await.ok_or(HeadlessError::NoAdapter)?;
+        let adapter_info = adapter.get_info();
+        println!("Headless adapter: {} ({:?}, {:?})", adapter_info.name, adapter_info.backend, adapter_info.device_type);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    // Software and GL adapters often miss the default limits.
+                    required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                },
+                None,
+            )
+            .await?;
+
+        let mut renderer = Renderer::new(
+            adapter,
+            device,
+            queue,
+            options.format,
+            (options.width, options.height),
+            options.asset_mode,
+            options.scene,
+        );
+        if options.sample_count != 1 {
+            renderer.set_sample_count(options.sample_count).map_err(HeadlessError::SampleCount)?;
+        }
+        renderer.init_render_passes().map_err(HeadlessError::Shader)?;
+        let (target, readback, padded_bytes_per_row) =
+            create_targets(renderer.device(), options.format, options.width, options.height);
+        Ok(Self {
+            renderer,
+            adapter_info,
+            target,
+            readback,
+            padded_bytes_per_row,
+        })
+    }
+
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.target.width(), self.target.height())
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) != self.size() && width > 0 && height > 0 {
+            (self.target, self.readback, self.padded_bytes_per_row) =
+                create_targets(self.renderer.device(), self.renderer.format(), width, height);
+            self.renderer.resize(width, height);
+        }
+    }
+
+    /// Draws one frame and waits until it has been copied back.
+    pub fn render(&mut self) -> Result<RenderedImage, HeadlessError> {
+        let (width, height) = self.size();
+        let view = self.target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.renderer.device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Encoder"),
+        });
+        self.renderer.render(&mut encoder, &view);
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            self.target.size(),
+        );
+        self.renderer.queue().submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let (tx, rx) = channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = tx.send(result);
+        });
+        self.renderer.device().poll(wgpu::Maintain::Wait);
+        rx.recv().expect("map_async callback dropped")?;
+
+        // Rows are padded to COPY_BYTES_PER_ROW_ALIGNMENT in the buffer.
+        let row_bytes = (width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row_bytes * height as usize);
+        {
+            let mapped = slice.get_mapped_range();
+            for row in mapped.chunks(self.padded_bytes_per_row as usize).take(height as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.readback.unmap();
+
+        Ok(RenderedImage {
+            width,
+            height,
+            format: self.renderer.format(),
+            pixels,
+        })
+    }
+}
+
+/// Hardware first unless told otherwise, then the software fallback, then whatever is left.
+async fn request_any_adapter(instance: &wgpu::Instance, options: &HeadlessOptions) -> Option<wgpu::Adapter> {
+    let fallback_modes: &[bool] = match options.force_fallback_adapter {
+        true => &[true],
+        false => &[false, true],
+    };
+    for &force_fallback_adapter in fallback_modes {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter,
+                compatible_surface: None,
+            })
+            .await;
+        if adapter.is_some() {
+            return adapter;
+        }
+    }
+    instance.enumerate_adapters(options.backends).into_iter().next()
+}
+
+fn create_targets(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::Buffer, u32) {
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Headless Target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Headless Readback"),
+        size: padded_bytes_per_row as u64 * height as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    (target, readback, padded_bytes_per_row)
+}
@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::headless::RenderedImage;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageError {
+    #[error("{path}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+    #[error("{path}: {source}")]
+    Decode { path: PathBuf, source: png::DecodingError },
+    #[error("{path}: {source}")]
+    Encode { path: PathBuf, source: png::EncodingError },
+    #[error("{path}: only 8-bit RGB and RGBA images are supported")]
+    UnsupportedPng { path: PathBuf },
+    #[error("{path}: cannot write {format:?} pixels, only RGBA8 and BGRA8")]
+    UnsupportedFormat { path: PathBuf, format: wgpu::TextureFormat },
+}
+
+/// Reads an 8-bit RGB or RGBA PNG as RGBA.
+pub fn read_png(path: &Path) -> Result<RenderedImage, ImageError> {
+    let file = File::open(path).map_err(|source| ImageError::Io { path: path.into(), source })?;
+    let decode = |source| ImageError::Decode { path: path.into(), source };
+    let mut reader = png::Decoder::new(BufReader::new(file)).read_info().map_err(decode)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(decode)?;
+    buf.truncate(info.buffer_size());
+    let pixels = match (info.color_type, info.bit_depth) {
+        (png::ColorType::Rgba, png::BitDepth::Eight) => buf,
+        (png::ColorType::Rgb, png::BitDepth::Eight) => {
+            buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect()
+        }
+        _ => return Err(ImageError::UnsupportedPng { path: path.into() }),
+    };
+    Ok(RenderedImage {
+        width: info.width,
+        height: info.height,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        pixels,
+    })
+}
+
+/// Writes an RGBA8 image, creating parent directories as needed. BGRA frames are swizzled.
+pub fn write_png(path: &Path, image: &RenderedImage) -> Result<(), ImageError> {
+    let pixels = image.to_rgba8().ok_or_else(|| ImageError::UnsupportedFormat {
+        path: path.into(),
+        format: image.format,
+    })?;
+    let io = |source| ImageError::Io { path: path.into(), source };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io)?;
+    }
+    let file = File::create(path).map_err(io)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let encode = |source| ImageError::Encode { path: path.into(), source };
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(encode)
+}
@@ -0,0 +1,242 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Matrix4, Vector3};
+
+use crate::scene::{EntityDesc, EntityId, LightDesc, LightId, MeshKind};
+
+/// Frames averaged for the FPS readout.
+const FRAME_WINDOW: usize = 60;
+
+/// What the inspector shows about the GPU and the surface.
+#[derive(Debug, Clone)]
+pub struct SurfaceInfo {
+    pub adapter: wgpu::AdapterInfo,
+    pub format: wgpu::TextureFormat,
+    pub present_mode: wgpu::PresentMode,
+    pub sample_count: u32,
+    pub supported_sample_counts: Vec<u32>,
+}
+
+/// A change made in the inspector, applied to the renderer once the frame's UI is done.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectorEdit {
+    Entity(EntityId, EntityDesc),
+    Light(LightId, LightDesc),
+    SampleCount(u32),
+}
+
+/// Debug panel with frame timing, adapter details and editable entities and lights. Toggled with F1.
+#[derive(Debug, Default)]
+pub struct Inspector {
+    pub open: bool,
+    frame_times: VecDeque<Duration>,
+}
+
+impl Inspector {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn record_frame(&mut self, dt: Duration) {
+        if self.frame_times.len() == FRAME_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
+    }
+
+    /// Average over the last `FRAME_WINDOW` frames.
+    pub fn frame_time(&self) -> Duration {
+        match self.frame_times.len() {
+            0 => Duration::ZERO,
+            n => self.frame_times.iter().sum::<Duration>() / n as u32,
+        }
+    }
+
+    pub fn fps(&self) -> f32 {
+        match self.frame_time().as_secs_f32() {
+            t if t > 0.0 => 1.0 / t,
+            _ => 0.0,
+        }
+    }
+
+    /// Draws the panel as a window over the scene and returns what was edited this frame.
+    pub fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        info: &SurfaceInfo,
+        entities: &[(EntityId, EntityDesc)],
+        lights: &[(LightId, LightDesc)],
+    ) -> Vec<InspectorEdit> {
+        let mut edits = Vec::new();
+        if !self.open {
+            return edits;
+        }
+        let stats = (self.frame_time(), self.fps());
+        egui::Window::new("Inspector").open(&mut self.open).show(ctx, |ui| {
+            panel(ui, stats, info, entities, lights, &mut edits);
+        });
+        edits
+    }
+
+    /// The same panel filling a window of its own.
+    pub fn panel_ui(
+        &mut self,
+        ctx: &egui::Context,
+        info: &SurfaceInfo,
+        entities: &[(EntityId, EntityDesc)],
+        lights: &[(LightId, LightDesc)],
+    ) -> Vec<InspectorEdit> {
+        let mut edits = Vec::new();
+        let stats = (self.frame_time(), self.fps());
+        egui::CentralPanel::default().show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                panel(ui, stats, info, entities, lights, &mut edits);
+            });
+        });
+        edits
+    }
+}
+
+fn panel(
+    ui: &mut egui::Ui,
+    (frame_time, fps): (Duration, f32),
+    info: &SurfaceInfo,
+    entities: &[(EntityId, EntityDesc)],
+    lights: &[(LightId, LightDesc)],
+    edits: &mut Vec<InspectorEdit>,
+) {
+    ui.label(format!("{:.2} ms, {fps:.0} FPS", frame_time.as_secs_f64() * 1000.0));
+    egui::CollapsingHeader::new("Device").default_open(true).show(ui, |ui| {
+        egui::Grid::new("device").num_columns(2).show(ui, |ui| {
+            ui.label("Adapter");
+            ui.label(&info.adapter.name);
+            ui.end_row();
+            ui.label("Backend");
+            ui.label(format!("{:?} ({:?})", info.adapter.backend, info.adapter.device_type));
+            ui.end_row();
+            ui.label("Driver");
+            ui.label(format!("{} {}", info.adapter.driver, info.adapter.driver_info));
+            ui.end_row();
+            ui.label("Surface format");
+            ui.label(format!("{:?}", info.format));
+            ui.end_row();
+            ui.label("Present mode");
+            ui.label(format!("{:?}", info.present_mode));
+            ui.end_row();
+            ui.label("MSAA");
+            let mut sample_count = info.sample_count;
+            egui::ComboBox::from_id_source("msaa")
+                .selected_text(format!("{sample_count}x"))
+                .show_ui(ui, |ui| {
+                    for &count in &info.supported_sample_counts {
+                        ui.selectable_value(&mut sample_count, count, format!("{count}x"));
+                    }
+                });
+            if sample_count != info.sample_count {
+                edits.push(InspectorEdit::SampleCount(sample_count));
+            }
+            ui.end_row();
+        });
+    });
+
+    egui::CollapsingHeader::new(format!("Entities ({})", entities.len())).show(ui, |ui| {
+        for (id, desc) in entities {
+            let mut edited = desc.clone();
+            ui.push_id(id, |ui| {
+                egui::CollapsingHeader::new(format!("{:?} {:?}", desc.mesh, id)).show(ui, |ui| {
+                    entity_ui(ui, &mut edited);
+                });
+            });
+            if edited != *desc {
+                edits.push(InspectorEdit::Entity(*id, edited));
+            }
+        }
+    });
+
+    egui::CollapsingHeader::new(format!("Lights ({})", lights.len())).show(ui, |ui| {
+        for (id, desc) in lights {
+            let mut edited = desc.clone();
+            ui.push_id(id, |ui| {
+                egui::CollapsingHeader::new(format!("{id:?}")).show(ui, |ui| {
+                    light_ui(ui, &mut edited);
+                });
+            });
+            if edited != *desc {
+                edits.push(InspectorEdit::Light(*id, edited));
+            }
+        }
+    });
+}
+
+fn entity_ui(ui: &mut egui::Ui, desc: &mut EntityDesc) {
+    egui::Grid::new("entity").num_columns(2).show(ui, |ui| {
+        ui.label("Mesh");
+        egui::ComboBox::from_id_source("mesh")
+            .selected_text(format!("{:?}", desc.mesh))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut desc.mesh, MeshKind::Cube, "Cube");
+                ui.selectable_value(&mut desc.mesh, MeshKind::Plane, "Plane");
+            });
+        ui.end_row();
+
+        // Only translation and uniform scale are exposed, the rotation is kept as is.
+        let mut translation = desc.mx_world.w.truncate();
+        let scale = desc.mx_world.x.truncate().magnitude();
+        let mut new_scale = scale;
+        ui.label("Position");
+        vec3_ui(ui, &mut translation);
+        ui.end_row();
+        ui.label("Scale");
+        ui.add(egui::DragValue::new(&mut new_scale).speed(0.01).range(0.01..=100.0));
+        ui.end_row();
+        if new_scale != scale && scale > 0.0 {
+            desc.mx_world = desc.mx_world * Matrix4::from_scale(new_scale / scale);
+        }
+        desc.mx_world.w = translation.extend(1.0);
+
+        ui.label("Rotation speed");
+        ui.add(egui::DragValue::new(&mut desc.rotation_speed).speed(0.01).suffix(" rad/s"));
+        ui.end_row();
+        ui.label("Color");
+        color_ui(ui, &mut desc.color);
+        ui.end_row();
+    });
+}
+
+fn light_ui(ui: &mut egui::Ui, desc: &mut LightDesc) {
+    egui::Grid::new("light").num_columns(2).show(ui, |ui| {
+        ui.label("Position");
+        let mut pos = Vector3::new(desc.pos.x, desc.pos.y, desc.pos.z);
+        vec3_ui(ui, &mut pos);
+        desc.pos = cgmath::Point3::new(pos.x, pos.y, pos.z);
+        ui.end_row();
+        ui.label("Color");
+        color_ui(ui, &mut desc.color);
+        ui.end_row();
+        ui.label("FOV");
+        ui.add(egui::Slider::new(&mut desc.fov, 1.0..=170.0).suffix("°"));
+        ui.end_row();
+        ui.label("Depth");
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut desc.depth.start).speed(0.05).range(0.01..=desc.depth.end));
+            ui.add(egui::DragValue::new(&mut desc.depth.end).speed(0.05).range(desc.depth.start..=1000.0));
+        });
+        ui.end_row();
+    });
+}
+
+fn vec3_ui(ui: &mut egui::Ui, v: &mut Vector3<f32>) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(&mut v.x).speed(0.05).prefix("x "));
+        ui.add(egui::DragValue::new(&mut v.y).speed(0.05).prefix("y "));
+        ui.add(egui::DragValue::new(&mut v.z).speed(0.05).prefix("z "));
+    });
+}
+
+fn color_ui(ui: &mut egui::Ui, color: &mut wgpu::Color) {
+    let mut rgb = [color.r as f32, color.g as f32, color.b as f32];
+    if ui.color_edit_button_rgb(&mut rgb).changed() {
+        (color.r, color.g, color.b) = (rgb[0] as f64, rgb[1] as f64, rgb[2] as f64);
+    }
+}
@@ -1,5 +1,28 @@
+// Lets the derive macros name `::glsl_naga` from inside this crate too.
+extern crate self as glsl_naga;
+
+pub mod app;
 pub mod application;
+pub mod camera;
+pub mod config;
+pub mod demo;
+pub mod depth;
+pub mod game_loop;
+pub mod golden;
 pub mod gui_tools;
+pub mod headless;
+pub mod hot_reload;
+pub mod image;
+pub mod inspector;
+pub mod msaa;
+pub mod reflection;
+pub mod renderer;
+pub mod scene;
+pub mod shader_cache;
+pub mod shader_editor;
+pub mod shadow;
 pub mod utils;
 pub mod vertex;
-mod data_stuct;
\ No newline at end of file
+pub mod vertex_layout;
+mod data_stuct;
+mod viewport;
@@ -0,0 +1,96 @@
+/// Sample counts wgpu accepts for render targets.
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+/// The sample counts every one of `formats` supports on `adapter`.
+pub fn supported_sample_counts(adapter: &wgpu::Adapter, formats: &[wgpu::TextureFormat]) -> Vec<u32> {
+    SAMPLE_COUNTS
+        .into_iter()
+        .filter(|&count| {
+            formats
+                .iter()
+                .all(|&format| adapter.get_texture_format_features(format).flags.sample_count_supported(count))
+        })
+        .collect()
+}
+
+/// Where a pass draws its color: straight into `view`, or into a multisampled texture
+/// that resolves into `view` at the end of the pass.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorTarget<'a> {
+    pub view: &'a wgpu::TextureView,
+    pub resolve_target: Option<&'a wgpu::TextureView>,
+}
+
+impl<'a> ColorTarget<'a> {
+    pub fn new(view: &'a wgpu::TextureView, msaa: Option<&'a MsaaTarget>) -> Self {
+        match msaa {
+            Some(msaa) => msaa.color_target(view),
+            None => Self {
+                view,
+                resolve_target: None,
+            },
+        }
+    }
+
+    /// The multisampled texture is stored as well, so a later pass (egui) can draw on top.
+    pub fn attachment(&self, load: wgpu::LoadOp<wgpu::Color>) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: self.view,
+            resolve_target: self.resolve_target,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+}
+
+/// Multisampled color texture matching the size of the surface.
+#[derive(Debug)]
+pub struct MsaaTarget {
+    sample_count: u32,
+    size: (u32, u32),
+    view: wgpu::TextureView,
+}
+
+impl MsaaTarget {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, sample_count: u32, size: (u32, u32)) -> Self {
+        let view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("MSAA Color Target"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        Self {
+            sample_count,
+            size,
+            view,
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Draw into this and resolve into `resolve_target`.
+    pub fn color_target<'a>(&'a self, resolve_target: &'a wgpu::TextureView) -> ColorTarget<'a> {
+        ColorTarget {
+            view: &self.view,
+            resolve_target: Some(resolve_target),
+        }
+    }
+}
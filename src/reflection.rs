@@ -0,0 +1,579 @@
+use std::collections::BTreeMap;
+use std::num::{NonZeroU32, NonZeroU64};
+
+use naga::valid::ModuleInfo;
+use naga::{AddressSpace, Binding, ImageClass, ImageDimension, Module, ScalarKind, ShaderStage, StorageFormat, TypeInner};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReflectionError {
+    #[error("no {stage:?} entry point named {name:?}")]
+    MissingEntryPoint { stage: ShaderStage, name: Option<String> },
+    #[error("{name} at location {location} has a type that cannot be shader I/O")]
+    UnsupportedIo { name: String, location: u32 },
+    #[error("resource {name} at group {group} binding {binding} has an unsupported type")]
+    UnsupportedResource { name: String, group: u32, binding: u32 },
+    #[error("group {group} binding {binding} is declared differently by two shaders")]
+    ConflictingBinding { group: u32, binding: u32 },
+}
+
+/// The scalar/vector type of a vertex input or fragment output as the shader sees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IoType {
+    pub kind: ScalarKind,
+    /// Bytes per component.
+    pub width: u8,
+    /// 1 for scalars, 2-4 for vectors.
+    pub components: u8,
+}
+
+impl IoType {
+    /// The vertex format that feeds this type without any conversion, e.g. `vec2` → `Float32x2`.
+    pub fn default_vertex_format(&self) -> Option<wgpu::VertexFormat> {
+        use wgpu::VertexFormat::*;
+        Some(match (self.kind, self.width, self.components) {
+            (ScalarKind::Float, 4, 1) => Float32,
+            (ScalarKind::Float, 4, 2) => Float32x2,
+            (ScalarKind::Float, 4, 3) => Float32x3,
+            (ScalarKind::Float, 4, 4) => Float32x4,
+            (ScalarKind::Float, 8, 1) => Float64,
+            (ScalarKind::Float, 8, 2) => Float64x2,
+            (ScalarKind::Float, 8, 3) => Float64x3,
+            (ScalarKind::Float, 8, 4) => Float64x4,
+            (ScalarKind::Sint, 4, 1) => Sint32,
+            (ScalarKind::Sint, 4, 2) => Sint32x2,
+            (ScalarKind::Sint, 4, 3) => Sint32x3,
+            (ScalarKind::Sint, 4, 4) => Sint32x4,
+            (ScalarKind::Uint, 4, 1) => Uint32,
+            (ScalarKind::Uint, 4, 2) => Uint32x2,
+            (ScalarKind::Uint, 4, 3) => Uint32x3,
+            (ScalarKind::Uint, 4, 4) => Uint32x4,
+            _ => return None,
+        })
+    }
+}
+
+/// WGSL spelling, e.g. `vec4<i32>`.
+impl std::fmt::Display for IoType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scalar = match (self.kind, self.width) {
+            (ScalarKind::Float, 8) => "f64",
+            (ScalarKind::Float, 2) => "f16",
+            (ScalarKind::Float, _) => "f32",
+            (ScalarKind::Sint, _) => "i32",
+            (ScalarKind::Uint, _) => "u32",
+            (ScalarKind::Bool, _) => "bool",
+            _ => "?",
+        };
+        match self.components {
+            1 => f.write_str(scalar),
+            n => write!(f, "vec{n}<{scalar}>"),
+        }
+    }
+}
+
+/// A `@location` input or output of an entry point.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderIo {
+    pub location: u32,
+    pub name: String,
+    pub ty: IoType,
+}
+
+/// A resource binding the entry point actually uses, with the layout entry wgpu needs for it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceBinding {
+    pub name: String,
+    pub group: u32,
+    pub entry: wgpu::BindGroupLayoutEntry,
+}
+
+/// Everything a pipeline needs to know about one entry point.
+#[derive(Debug, Clone)]
+pub struct ShaderReflection {
+    pub stage: ShaderStage,
+    pub entry_point: String,
+    /// Vertex stage inputs, sorted by location.
+    pub vertex_inputs: Vec<ShaderIo>,
+    /// Sorted by (group, binding).
+    pub resources: Vec<ResourceBinding>,
+    /// Size in bytes of the push constant block, if the entry point uses one.
+    pub push_constant_size: Option<u32>,
+    /// Fragment stage outputs, sorted by location.
+    pub fragment_outputs: Vec<ShaderIo>,
+}
+
+impl ShaderReflection {
+    /// Reflects the `stage` entry point, picking it by name when the module has several.
+    pub fn new(
+        module: &Module,
+        info: &ModuleInfo,
+        stage: ShaderStage,
+        entry_point: Option<&str>,
+    ) -> Result<Self, ReflectionError> {
+        let (index, ep) = module
+            .entry_points
+            .iter()
+            .enumerate()
+            .find(|(_, ep)| ep.stage == stage && entry_point.is_none_or(|name| ep.name == name))
+            .ok_or_else(|| ReflectionError::MissingEntryPoint {
+                stage,
+                name: entry_point.map(str::to_string),
+            })?;
+
+        let mut io = Vec::new();
+        for arg in &ep.function.arguments {
+            collect_io(module, arg.name.as_deref(), arg.ty, arg.binding.as_ref(), &mut io)?;
+        }
+        let vertex_inputs = match stage {
+            ShaderStage::Vertex => sorted(io),
+            _ => Vec::new(),
+        };
+
+        let mut outputs = Vec::new();
+        if let Some(result) = &ep.function.result {
+            collect_io(module, None, result.ty, result.binding.as_ref(), &mut outputs)?;
+        }
+        let fragment_outputs = match stage {
+            ShaderStage::Fragment => sorted(outputs),
+            _ => Vec::new(),
+        };
+
+        let visibility = match stage {
+            ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+            ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+            ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+        };
+        let function_info = info.get_entry_point(index);
+        let mut resources = Vec::new();
+        let mut push_constant_size = None;
+        for (handle, var) in module.global_variables.iter() {
+            if function_info[handle].is_empty() {
+                continue;
+            }
+            if var.space == AddressSpace::PushConstant {
+                push_constant_size = Some(module.types[var.ty].inner.size(module.to_ctx()));
+                continue;
+            }
+            let Some(binding) = &var.binding else { continue };
+            // Uniform blocks without an instance name are only known by their block name.
+            let name = var.name.clone().or_else(|| module.types[var.ty].name.clone()).unwrap_or_default();
+            let unsupported = || ReflectionError::UnsupportedResource {
+                name: name.clone(),
+                group: binding.group,
+                binding: binding.binding,
+            };
+            let (ty, count) = match module.types[var.ty].inner {
+                TypeInner::BindingArray { base, size } => (base, array_count(size)),
+                _ => (var.ty, None),
+            };
+            let binding_type = binding_type(module, var.space, ty).ok_or_else(unsupported)?;
+            resources.push(ResourceBinding {
+                name,
+                group: binding.group,
+                entry: wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility,
+                    ty: binding_type,
+                    count,
+                },
+            });
+        }
+        resources.sort_by_key(|r| (r.group, r.entry.binding));
+
+        Ok(Self {
+            stage,
+            entry_point: ep.name.clone(),
+            vertex_inputs,
+            resources,
+            push_constant_size,
+            fragment_outputs,
+        })
+    }
+
+    /// Tightly packed attributes in location order, using each input's default format.
+    /// Useful when the vertex data is authored to match the shader exactly.
+    pub fn packed_vertex_attributes(&self) -> Result<(Vec<wgpu::VertexAttribute>, wgpu::BufferAddress), ReflectionError> {
+        let mut offset = 0;
+        let mut attributes = Vec::new();
+        for input in &self.vertex_inputs {
+            let format = input.ty.default_vertex_format().ok_or_else(|| ReflectionError::UnsupportedIo {
+                name: input.name.clone(),
+                location: input.location,
+            })?;
+            attributes.push(wgpu::VertexAttribute {
+                format,
+                offset,
+                shader_location: input.location,
+            });
+            offset += format.size();
+        }
+        Ok((attributes, offset))
+    }
+}
+
+/// The stages of one pipeline merged together: shared bindings get the union of their visibilities.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineReflection {
+    pub vertex_inputs: Vec<ShaderIo>,
+    /// group → binding → entry.
+    pub bind_groups: BTreeMap<u32, BTreeMap<u32, wgpu::BindGroupLayoutEntry>>,
+    pub push_constant_ranges: Vec<wgpu::PushConstantRange>,
+    pub fragment_outputs: Vec<ShaderIo>,
+}
+
+impl PipelineReflection {
+    pub fn new<'a>(stages: impl IntoIterator<Item = &'a ShaderReflection>) -> Result<Self, ReflectionError> {
+        let mut pipeline = Self::default();
+        for stage in stages {
+            pipeline.vertex_inputs.extend(stage.vertex_inputs.iter().cloned());
+            pipeline.fragment_outputs.extend(stage.fragment_outputs.iter().cloned());
+            for resource in &stage.resources {
+                let group = pipeline.bind_groups.entry(resource.group).or_default();
+                match group.get_mut(&resource.entry.binding) {
+                    Some(entry) if entry.ty == resource.entry.ty && entry.count == resource.entry.count => {
+                        entry.visibility |= resource.entry.visibility;
+                    }
+                    Some(_) => {
+                        return Err(ReflectionError::ConflictingBinding {
+                            group: resource.group,
+                            binding: resource.entry.binding,
+                        })
+                    }
+                    None => {
+                        group.insert(resource.entry.binding, resource.entry);
+                    }
+                }
+            }
+            if let Some(size) = stage.push_constant_size {
+                let stages = match stage.stage {
+                    ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                    ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                    ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                };
+                pipeline.push_constant_ranges.push(wgpu::PushConstantRange { stages, range: 0..size });
+            }
+        }
+        Ok(pipeline)
+    }
+
+    /// Takes over `group` from another pipeline so both can share one bind group layout.
+    /// Every binding this pipeline uses in that group must be declared the same way there.
+    pub fn adopt_group(&mut self, group: u32, from: &PipelineReflection) -> Result<(), ReflectionError> {
+        let theirs = from.bind_groups.get(&group).cloned().unwrap_or_default();
+        for (binding, entry) in self.bind_groups.get(&group).into_iter().flatten() {
+            match theirs.get(binding) {
+                Some(other) if other.ty == entry.ty && other.count == entry.count => {}
+                _ => return Err(ReflectionError::ConflictingBinding { group, binding: *binding }),
+            }
+        }
+        self.bind_groups.insert(group, theirs);
+        Ok(())
+    }
+
+    pub fn bind_group_layout_entries(&self, group: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.bind_groups
+            .get(&group)
+            .map(|entries| entries.values().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// One layout per group index up to the highest used group; unused groups get an empty layout.
+    pub fn create_bind_group_layouts(&self, device: &wgpu::Device, label: Option<&str>) -> Vec<wgpu::BindGroupLayout> {
+        let group_count = self.bind_groups.keys().next_back().map_or(0, |g| g + 1);
+        (0..group_count)
+            .map(|group| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label,
+                    entries: &self.bind_group_layout_entries(group),
+                })
+            })
+            .collect()
+    }
+
+    pub fn create_pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        bind_group_layouts: &[wgpu::BindGroupLayout],
+        label: Option<&str>,
+    ) -> wgpu::PipelineLayout {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &self.push_constant_ranges,
+        })
+    }
+}
+
+fn sorted(mut io: Vec<ShaderIo>) -> Vec<ShaderIo> {
+    io.sort_by_key(|io| io.location);
+    io
+}
+
+/// Gathers `@location` bindings, descending into structs the way WGSL entry points use them.
+fn collect_io(
+    module: &Module,
+    name: Option<&str>,
+    ty: naga::Handle<naga::Type>,
+    binding: Option<&Binding>,
+    out: &mut Vec<ShaderIo>,
+) -> Result<(), ReflectionError> {
+    match (binding, &module.types[ty].inner) {
+        (Some(Binding::Location { location, .. }), inner) => {
+            let name = name.unwrap_or_default().to_string();
+            let ty = match *inner {
+                TypeInner::Scalar(scalar) => IoType {
+                    kind: scalar.kind,
+                    width: scalar.width,
+                    components: 1,
+                },
+                TypeInner::Vector { size, scalar } => IoType {
+                    kind: scalar.kind,
+                    width: scalar.width,
+                    components: size as u8,
+                },
+                _ => return Err(ReflectionError::UnsupportedIo { name, location: *location }),
+            };
+            out.push(ShaderIo {
+                location: *location,
+                name,
+                ty,
+            });
+        }
+        (None, TypeInner::Struct { members, .. }) => {
+            for member in members {
+                collect_io(module, member.name.as_deref(), member.ty, member.binding.as_ref(), out)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn array_count(size: naga::ArraySize) -> Option<NonZeroU32> {
+    match size {
+        naga::ArraySize::Constant(count) => Some(count),
+        naga::ArraySize::Dynamic => None,
+    }
+}
+
+fn binding_type(module: &Module, space: AddressSpace, ty: naga::Handle<naga::Type>) -> Option<wgpu::BindingType> {
+    let inner = &module.types[ty].inner;
+    Some(match space {
+        AddressSpace::Uniform => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(inner.size(module.to_ctx()) as u64),
+        },
+        AddressSpace::Storage { access } => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !access.contains(naga::StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            // Runtime-sized arrays report only their fixed part, which is the minimum wgpu wants.
+            min_binding_size: NonZeroU64::new(inner.size(module.to_ctx()) as u64),
+        },
+        AddressSpace::Handle => match *inner {
+            TypeInner::Sampler { comparison: true } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+            }
+            TypeInner::Sampler { comparison: false } => {
+                wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+            }
+            TypeInner::Image { dim, arrayed, class } => {
+                let view_dimension = view_dimension(dim, arrayed)?;
+                match class {
+                    ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                        sample_type: match kind {
+                            ScalarKind::Float => wgpu::TextureSampleType::Float { filterable: !multi },
+                            ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                            ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                            _ => return None,
+                        },
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension,
+                        multisampled: multi,
+                    },
+                    ImageClass::Storage { format, access } => wgpu::BindingType::StorageTexture {
+                        access: match (
+                            access.contains(naga::StorageAccess::LOAD),
+                            access.contains(naga::StorageAccess::STORE),
+                        ) {
+                            (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                            (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                            _ => wgpu::StorageTextureAccess::WriteOnly,
+                        },
+                        format: storage_format(format),
+                        view_dimension,
+                    },
+                }
+            }
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> Option<wgpu::TextureViewDimension> {
+    Some(match (dim, arrayed) {
+        (ImageDimension::D1, false) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, false) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+        _ => return None,
+    })
+}
+
+fn storage_format(format: StorageFormat) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat as Tf;
+    match format {
+        StorageFormat::R8Unorm => Tf::R8Unorm,
+        StorageFormat::R8Snorm => Tf::R8Snorm,
+        StorageFormat::R8Uint => Tf::R8Uint,
+        StorageFormat::R8Sint => Tf::R8Sint,
+        StorageFormat::R16Uint => Tf::R16Uint,
+        StorageFormat::R16Sint => Tf::R16Sint,
+        StorageFormat::R16Float => Tf::R16Float,
+        StorageFormat::Rg8Unorm => Tf::Rg8Unorm,
+        StorageFormat::Rg8Snorm => Tf::Rg8Snorm,
+        StorageFormat::Rg8Uint => Tf::Rg8Uint,
+        StorageFormat::Rg8Sint => Tf::Rg8Sint,
+        StorageFormat::R32Uint => Tf::R32Uint,
+        StorageFormat::R32Sint => Tf::R32Sint,
+        StorageFormat::R32Float => Tf::R32Float,
+        StorageFormat::Rg16Uint => Tf::Rg16Uint,
+        StorageFormat::Rg16Sint => Tf::Rg16Sint,
+        StorageFormat::Rg16Float => Tf::Rg16Float,
+        StorageFormat::Rgba8Unorm => Tf::Rgba8Unorm,
+        StorageFormat::Rgba8Snorm => Tf::Rgba8Snorm,
+        StorageFormat::Rgba8Uint => Tf::Rgba8Uint,
+        StorageFormat::Rgba8Sint => Tf::Rgba8Sint,
+        StorageFormat::Bgra8Unorm => Tf::Bgra8Unorm,
+        StorageFormat::Rgb10a2Uint => Tf::Rgb10a2Uint,
+        StorageFormat::Rgb10a2Unorm => Tf::Rgb10a2Unorm,
+        StorageFormat::Rg11b10Float => Tf::Rg11b10Float,
+        StorageFormat::Rg32Uint => Tf::Rg32Uint,
+        StorageFormat::Rg32Sint => Tf::Rg32Sint,
+        StorageFormat::Rg32Float => Tf::Rg32Float,
+        StorageFormat::Rgba16Uint => Tf::Rgba16Uint,
+        StorageFormat::Rgba16Sint => Tf::Rgba16Sint,
+        StorageFormat::Rgba16Float => Tf::Rgba16Float,
+        StorageFormat::Rgba32Uint => Tf::Rgba32Uint,
+        StorageFormat::Rgba32Sint => Tf::Rgba32Sint,
+        StorageFormat::Rgba32Float => Tf::Rgba32Float,
+        StorageFormat::R16Unorm => Tf::R16Unorm,
+        StorageFormat::R16Snorm => Tf::R16Snorm,
+        StorageFormat::Rg16Unorm => Tf::Rg16Unorm,
+        StorageFormat::Rg16Snorm => Tf::Rg16Snorm,
+        StorageFormat::Rgba16Unorm => Tf::Rgba16Unorm,
+        StorageFormat::Rgba16Snorm => Tf::Rgba16Snorm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{parse_module, SourceLang, TranslateOptions};
+
+    fn reflect(source: &str, stage: ShaderStage) -> ShaderReflection {
+        let (module, info) = parse_module(source, SourceLang::Glsl(stage), &TranslateOptions::default()).unwrap();
+        ShaderReflection::new(&module, &info, stage, None).unwrap()
+    }
+
+    fn bake() -> ShaderReflection {
+        reflect(include_str!("../assets/glsl-in/bake.vert"), ShaderStage::Vertex)
+    }
+
+    fn forward() -> PipelineReflection {
+        let vs = reflect(include_str!("../assets/glsl-in/shader.vert"), ShaderStage::Vertex);
+        let fs = reflect(include_str!("../assets/glsl-in/shader.frag"), ShaderStage::Fragment);
+        PipelineReflection::new([&vs, &fs]).unwrap()
+    }
+
+    fn uniform(size: u64) -> wgpu::BindingType {
+        wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size),
+        }
+    }
+
+    #[test]
+    fn forward_bind_groups() {
+        let forward = forward();
+        let both = wgpu::ShaderStages::VERTEX_FRAGMENT;
+        let globals = forward.bind_group_layout_entries(0);
+        let types: Vec<_> = globals.iter().map(|e| (e.binding, e.visibility, e.ty)).collect();
+        assert_eq!(
+            types,
+            [
+                // mat4 + uvec4
+                (0, both, uniform(80)),
+                // 10 个 Light,每个 mat4 + 2 * vec4
+                (1, wgpu::ShaderStages::FRAGMENT, uniform(960)),
+                (
+                    2,
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    }
+                ),
+                (
+                    3,
+                    wgpu::ShaderStages::FRAGMENT,
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison)
+                ),
+            ]
+        );
+        let entity = forward.bind_group_layout_entries(1);
+        assert_eq!(entity.len(), 1);
+        assert_eq!((entity[0].visibility, entity[0].ty), (both, uniform(80)));
+        assert!(forward.push_constant_ranges.is_empty());
+    }
+
+    #[test]
+    fn vertex_inputs() {
+        let formats = |io: &[ShaderIo]| -> Vec<_> {
+            io.iter().map(|io| (io.location, io.name.clone(), io.ty.default_vertex_format())).collect()
+        };
+        let sint4 = Some(wgpu::VertexFormat::Sint32x4);
+        assert_eq!(formats(&bake().vertex_inputs), [(0, "a_Pos".to_string(), sint4)]);
+        assert_eq!(
+            formats(&forward().vertex_inputs),
+            [(0, "a_Pos".to_string(), sint4), (1, "a_Normal".to_string(), sint4)]
+        );
+        assert_eq!(forward().fragment_outputs.len(), 1);
+    }
+
+    #[test]
+    fn adopt_group_takes_the_merged_visibility() {
+        let forward = forward();
+        let mut shadow = PipelineReflection::new([&bake()]).unwrap();
+        let entity = shadow.bind_group_layout_entries(1);
+        assert_eq!(entity[0].visibility, wgpu::ShaderStages::VERTEX);
+
+        shadow.adopt_group(1, &forward).unwrap();
+        assert_eq!(shadow.bind_group_layout_entries(1), forward.bind_group_layout_entries(1));
+        assert_eq!(shadow.bind_group_layout_entries(1)[0].visibility, wgpu::ShaderStages::VERTEX_FRAGMENT);
+        // 只接管第 1 组,第 0 组保持 bake.vert 自己的
+        assert_eq!(shadow.bind_group_layout_entries(0).len(), 1);
+    }
+
+    #[test]
+    fn adopt_group_rejects_conflicts() {
+        let mut forward = forward();
+        let shadow = PipelineReflection::new([&bake()]).unwrap();
+        // bake.vert 的 Globals 只有 64 字节,和 forward 的不一样
+        assert_eq!(
+            forward.adopt_group(0, &shadow),
+            Err(ReflectionError::ConflictingBinding { group: 0, binding: 0 })
+        );
+    }
+}
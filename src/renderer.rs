@@ -0,0 +1,710 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::depth::DepthTest;
+use crate::msaa::{self, ColorTarget, MsaaTarget};
+use crate::data_stuct::{MeshPass, State};
+use crate::reflection::{PipelineReflection, ShaderReflection};
+use crate::scene::{EntityDesc, EntityId, LightDesc, LightId, SceneError};
+use crate::shader_cache::ShaderCache;
+use crate::shadow;
+use crate::utils::{SourceLang, TranslateOptions};
+use crate::vertex_layout::{check_vertex_buffers, VertexLayout};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, VertexLayout)]
+struct Vertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+
+const VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [-0.0868241, 0.49240386],
+        color: [0.5, 0.0, 0.5, 1.0],
+    }, // A
+    Vertex {
+        position: [-0.49513406, 0.06958647],
+        color: [0.5, 0.0, 0.5, 1.0],
+    }, // B
+    Vertex {
+        position: [-0.21918549, -0.44939706],
+        color: [0.5, 0.0, 0.5, 1.0],
+    }, // C
+    Vertex {
+        position: [0.35966998, -0.3473291],
+        color: [0.5, 0.0, 0.5, 1.0],
+    }, // D
+    Vertex {
+        position: [0.44147372, 0.2347359],
+        color: [0.5, 0.0, 0.5, 1.0],
+    }, // E
+];
+
+const INDICES: &[u16] = &[0, 1, 4, 1, 2, 4, 2, 3, 4];
+
+/// A GLSL stage loaded from `assets/<name>`, with a copy baked into the binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderFile {
+    pub name: &'static str,
+    pub embedded: &'static str,
+    pub stage: naga::ShaderStage,
+}
+
+const PENTAGON_SHADERS: [ShaderFile; 2] = [
+    ShaderFile {
+        name: "shader.vert",
+        embedded: include_str!("../assets/shader.vert"),
+        stage: naga::ShaderStage::Vertex,
+    },
+    ShaderFile {
+        name: "shader.frag",
+        embedded: include_str!("../assets/shader.frag"),
+        stage: naga::ShaderStage::Fragment,
+    },
+];
+
+pub const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.3,
+    a: 1.0,
+};
+
+/// Where the renderer gets its shaders from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetMode {
+    /// Sources baked into the binary with `include_str!`.
+    Embedded,
+    /// Sources read from this directory at startup and rebuilt whenever they change on disk.
+    Runtime(PathBuf),
+}
+
+/// What the renderer draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Scene {
+    /// `VERTICES`/`INDICES` with `assets/shader.vert` and `assets/shader.frag`.
+    Pentagon,
+    /// Cubes on a plane lit by shadow-casting lights, using the shaders in `assets/glsl-in`.
+    #[default]
+    Shadow,
+}
+
+impl Scene {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::Pentagon => "pentagon",
+            Scene::Shadow => "shadow",
+        }
+    }
+}
+
+// Only one of these exists per renderer, so the size difference does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+enum SceneState {
+    Pentagon(MeshPass),
+    Shadow(State),
+}
+
+/// A compiled shader stage and what reflection found in it.
+#[derive(Debug)]
+pub(crate) struct ShaderStageModule {
+    pub module: Arc<wgpu::ShaderModule>,
+    pub reflection: ShaderReflection,
+}
+
+/// The GPU side of the app: device, shaders and render passes, independent of where the
+/// frame ends up. A window presents it to a surface, headless mode to an offscreen texture.
+#[derive(Debug)]
+pub struct Renderer {
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// Format of the color target every frame is drawn into.
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    scene: Scene,
+    shader_cache: ShaderCache,
+    asset_mode: AssetMode,
+    shader_error: Option<String>,
+    /// Sources edited in the app, by shader name, used instead of the files.
+    shader_overrides: BTreeMap<String, String>,
+    states: Option<SceneState>,
+    /// The scene from before the device was lost, until its passes are rebuilt on the new device.
+    lost_states: Option<SceneState>,
+    camera: Camera,
+    /// The camera as it was before the last update step.
+    previous_camera: Camera,
+    /// Where the drawn frame falls between the last two update steps, see [`Self::set_interpolation_alpha`].
+    interpolation_alpha: f32,
+    depth_test: Option<DepthTest>,
+    sample_count: u32,
+    /// Only present while `sample_count > 1`.
+    msaa_target: Option<MsaaTarget>,
+    /// Set from the device-lost callback, which may run on any thread.
+    device_lost: Arc<AtomicBool>,
+    /// What the device was created with; a lost device can no longer be asked.
+    device_features: wgpu::Features,
+    device_limits: wgpu::Limits,
+}
+
+impl Renderer {
+    pub fn new(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        format: wgpu::TextureFormat,
+        size: (u32, u32),
+        asset_mode: AssetMode,
+        scene: Scene,
+    ) -> Self {
+        let device_lost = watch_device_lost(&device);
+        let (device_features, device_limits) = (device.features(), device.limits());
+        let camera = Camera {
+            aspect: size.0 as f32 / size.1.max(1) as f32,
+            ..Default::default()
+        };
+        Self {
+            adapter,
+            device,
+            queue,
+            format,
+            size,
+            scene,
+            shader_cache: ShaderCache::default(),
+            asset_mode,
+            shader_error: None,
+            shader_overrides: BTreeMap::new(),
+            states: None,
+            lost_states: None,
+            previous_camera: camera.clone(),
+            camera,
+            interpolation_alpha: 1.0,
+            depth_test: Some(DepthTest::default()),
+            sample_count: 1,
+            msaa_target: None,
+            device_lost,
+            device_features,
+            device_limits,
+        }
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    pub fn asset_mode(&self) -> &AssetMode {
+        &self.asset_mode
+    }
+
+    /// Keeps translated shaders in `dir` between runs, or only in memory with `None` (the default).
+    pub fn set_shader_cache_dir(&mut self, dir: Option<PathBuf>) {
+        self.shader_cache.set_disk_cache(dir);
+    }
+
+    /// Depth test of the forward pass, `None` draws in submission order without a depth buffer.
+    pub fn depth_test(&self) -> Option<DepthTest> {
+        self.depth_test
+    }
+
+    /// Rebuilds the pipelines and the depth buffer for the new depth test.
+    pub fn set_depth_test(&mut self, depth_test: Option<DepthTest>) -> Result<(), String> {
+        if let Some(format) = depth_test.map(|test| test.format).filter(|format| !format.is_depth_stencil_format()) {
+            return Err(format!("{format:?} is not a depth format"));
+        }
+        self.depth_test = depth_test;
+        match self.states {
+            Some(_) => self.reload_shaders(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Counts the adapter supports for both the color target and the depth buffer.
+    pub fn supported_sample_counts(&self) -> Vec<u32> {
+        let formats: Vec<_> = std::iter::once(self.format)
+            .chain(self.depth_test.map(|test| test.format))
+            .collect();
+        msaa::supported_sample_counts(&self.adapter, &formats)
+    }
+
+    /// Switches MSAA on (2, 4 or 8) or off (1), rebuilding the pipelines and targets.
+    /// Anything else drawing into [`Self::color_target`], like the GUI, must follow.
+    pub fn set_sample_count(&mut self, sample_count: u32) -> Result<(), String> {
+        if !self.supported_sample_counts().contains(&sample_count) {
+            return Err(format!(
+                "{sample_count}x MSAA is not supported for {:?}, pick one of {:?}",
+                self.format,
+                self.supported_sample_counts()
+            ));
+        }
+        self.sample_count = sample_count;
+        self.msaa_target = self.create_msaa_target();
+        match self.states {
+            Some(_) => self.reload_shaders(),
+            None => Ok(()),
+        }
+    }
+
+    fn create_msaa_target(&self) -> Option<MsaaTarget> {
+        (self.sample_count > 1).then(|| MsaaTarget::new(&self.device, self.format, self.sample_count, self.size))
+    }
+
+    /// What to draw into for a frame ending up in `view`: `view` itself, or the MSAA target resolving into it.
+    pub fn color_target<'a>(&'a self, view: &'a wgpu::TextureView) -> ColorTarget<'a> {
+        ColorTarget::new(view, self.msaa_target.as_ref())
+    }
+
+    /// Written into `u_ViewProj` every frame. The pentagon ignores it and stays in clip space.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// The last shader compile error, cleared by the next successful reload.
+    pub fn shader_error(&self) -> Option<&str> {
+        self.shader_error.as_deref()
+    }
+
+    /// True once the driver reported the device as lost; nothing should be drawn until
+    /// [`Self::recreate_device`] succeeded.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Acquire)
+    }
+
+    /// Requests a new device from the same adapter and rebuilds every GPU resource on it.
+    /// Entities, lights, the camera and shader edits carry over. If the passes fail to build,
+    /// the scene is kept and restored by the next successful [`Self::reload_shaders`].
+    pub fn recreate_device(&mut self) -> Result<(), String> {
+        println!("Recreating device");
+        let descriptor = wgpu::DeviceDescriptor {
+            label: None,
+            required_features: self.device_features,
+            required_limits: self.device_limits.clone(),
+        };
+        let (device, queue) = block_on(self.adapter.request_device(&descriptor, None))
+            .map_err(|e| format!("failed to recreate the device: {e}"))?;
+        // 旧设备上的资源不能再画,场景先留着,通道重建成功后再恢复。
+        // 重建失败时(比如着色器改坏了)下一次 reload 成功后恢复。
+        if let Some(states) = self.states.take() {
+            self.lost_states = Some(states);
+        }
+        self.device_lost = watch_device_lost(&device);
+        self.device = device;
+        self.queue = queue;
+        // Cached modules belong to the old device, the translated sources stay valid
+        self.shader_cache.clear_modules();
+        self.msaa_target = self.create_msaa_target();
+        self.init_render_passes()
+    }
+
+    /// Builds the passes. On a shader error the renderer keeps running and only clears the target.
+    pub fn init_render_passes(&mut self) -> Result<(), String> {
+        println!("Initializing");
+        let states = match self.scene {
+            Scene::Pentagon => self.create_pentagon_pass().map(SceneState::Pentagon),
+            Scene::Shadow => shadow::create_state(self).map(SceneState::Shadow),
+        };
+        let mut states = states.map_err(|report| self.set_shader_error(report))?;
+        if let (SceneState::Shadow(state), Some(SceneState::Shadow(old))) = (&mut states, &self.lost_states) {
+            shadow::restore(state, &self.device, old);
+        }
+        self.lost_states = None;
+        self.states = Some(states);
+        self.shader_error = None;
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines after a shader file changed, keeping the old ones on failure.
+    pub fn reload_shaders(&mut self) -> Result<(), String> {
+        let Some(mut states) = self.states.take() else {
+            return self.init_render_passes();
+        };
+        let result = match &mut states {
+            SceneState::Pentagon(pass) => self.create_pentagon_pipeline().map(|pipeline| pass.pipeline = pipeline),
+            SceneState::Shadow(state) => shadow::reload_pipelines(self, state),
+        };
+        self.states = Some(states);
+        result.map_err(|report| self.set_shader_error(report))?;
+        self.shader_error = None;
+        Ok(())
+    }
+
+    fn set_shader_error(&mut self, report: String) -> String {
+        eprintln!("{report}");
+        self.shader_error = Some(report.clone());
+        report
+    }
+
+    /// Resizes the size-dependent targets. The color target itself belongs to the caller.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.size = (width, height);
+        self.camera.set_aspect(width, height);
+        if self.msaa_target.as_ref().is_some_and(|msaa| msaa.size() != self.size) {
+            self.msaa_target = self.create_msaa_target();
+        }
+        if let Some(SceneState::Shadow(state)) = &mut self.states {
+            shadow::resize(state, &self.device, self.size);
+        }
+    }
+
+    /// One simulation step: spins entities by their `rotation_speed` over `dt`. The camera as it
+    /// is now becomes the step's starting point, so move it after calling this.
+    pub fn update(&mut self, dt: Duration) {
+        self.previous_camera = self.camera.clone();
+        if let Some(SceneState::Shadow(state)) = &mut self.states {
+            shadow::update(state, dt.as_secs_f32());
+        }
+    }
+
+    /// The entity scene together with the device, since spawning needs both.
+    fn shadow_state(&mut self) -> Result<(&mut State, &wgpu::Device), SceneError> {
+        match &mut self.states {
+            Some(SceneState::Shadow(state)) => Ok((state, &self.device)),
+            Some(SceneState::Pentagon(_)) => Err(SceneError::Unsupported(self.scene)),
+            None if self.scene == Scene::Shadow => Err(SceneError::NotLoaded),
+            None => Err(SceneError::Unsupported(self.scene)),
+        }
+    }
+
+    pub fn spawn_entity(&mut self, desc: EntityDesc) -> Result<EntityId, SceneError> {
+        let (state, device) = self.shadow_state()?;
+        Ok(shadow::spawn_entity(state, device, desc))
+    }
+
+    pub fn despawn_entity(&mut self, id: EntityId) -> Result<(), SceneError> {
+        shadow::despawn_entity(self.shadow_state()?.0, id)
+    }
+
+    /// Edits an entity in place. Changing `mesh` swaps in the shared buffers of the new mesh.
+    pub fn update_entity(&mut self, id: EntityId, f: impl FnOnce(&mut EntityDesc)) -> Result<(), SceneError> {
+        let (state, device) = self.shadow_state()?;
+        shadow::update_entity(state, device, id, f)
+    }
+
+    /// Current entities in draw order, empty for scenes without any.
+    pub fn entities(&self) -> Vec<(EntityId, EntityDesc)> {
+        match &self.states {
+            Some(SceneState::Shadow(state)) => state.entities.iter().map(|entity| (entity.id, entity.desc())).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Fails with [`SceneError::TooManyLights`] once all `MAX_LIGHTS` shadow layers are taken.
+    pub fn add_light(&mut self, desc: LightDesc) -> Result<LightId, SceneError> {
+        shadow::add_light(self.shadow_state()?.0, desc)
+    }
+
+    pub fn remove_light(&mut self, id: LightId) -> Result<(), SceneError> {
+        shadow::remove_light(self.shadow_state()?.0, id)
+    }
+
+    /// Edits a light in place; the light uniforms are re-uploaded on the next frame.
+    pub fn update_light(&mut self, id: LightId, f: impl FnOnce(&mut LightDesc)) -> Result<(), SceneError> {
+        shadow::update_light(self.shadow_state()?.0, id, f)
+    }
+
+    pub fn move_light(&mut self, id: LightId, pos: cgmath::Point3<f32>) -> Result<(), SceneError> {
+        self.update_light(id, |desc| desc.pos = pos)
+    }
+
+    /// Current lights in shadow-layer order, empty for scenes without any.
+    pub fn lights(&self) -> Vec<(LightId, LightDesc)> {
+        match &self.states {
+            Some(SceneState::Shadow(state)) => state.lights.iter().map(|light| (light.id, light.desc())).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// How far between the previous update step (0) and the latest one (1) the next frame is drawn.
+    /// Defaults to 1, drawing the latest step as is.
+    pub fn set_interpolation_alpha(&mut self, alpha: f32) {
+        self.interpolation_alpha = alpha.clamp(0.0, 1.0);
+    }
+
+    /// Records every pass of one frame into `view`, which must have the renderer's format.
+    pub fn render(&mut self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let target = ColorTarget::new(view, self.msaa_target.as_ref());
+        let alpha = self.interpolation_alpha;
+        match &mut self.states {
+            Some(SceneState::Shadow(state)) => {
+                let camera = self.previous_camera.lerp(&self.camera, alpha);
+                shadow::render(state, &camera, alpha, &self.queue, encoder, target)
+            }
+            Some(SceneState::Pentagon(pass)) => render_pentagon(pass, encoder, target),
+            // 着色器编译失败时 states 为空,只清屏
+            None => {
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Clear Pass"),
+                    color_attachments: &[Some(target.attachment(wgpu::LoadOp::Clear(CLEAR_COLOR)))],
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// The GLSL stages the current scene is built from.
+    pub fn shader_files(&self) -> &'static [ShaderFile] {
+        match self.scene {
+            Scene::Pentagon => &PENTAGON_SHADERS,
+            Scene::Shadow => &shadow::SHADERS,
+        }
+    }
+
+    /// Preprocessor defines the current scene compiles its shaders with.
+    pub fn shader_defines(&self) -> BTreeMap<String, String> {
+        match self.scene {
+            Scene::Pentagon => BTreeMap::new(),
+            Scene::Shadow => shadow::defines(),
+        }
+    }
+
+    /// Translation options matching what the scene's shaders are compiled with.
+    pub fn translate_options(&self) -> TranslateOptions {
+        let mut options = self.shader_cache.options().clone();
+        options.preprocess.defines.extend(self.shader_defines());
+        options
+    }
+
+    /// Source of `file`: an in-app edit if there is one, then the asset directory in runtime
+    /// mode, then the copy baked into the binary.
+    pub fn shader_source(&self, file: &ShaderFile) -> Result<String, String> {
+        if let Some(source) = self.shader_overrides.get(file.name) {
+            return Ok(source.clone());
+        }
+        match &self.asset_mode {
+            AssetMode::Embedded => Ok(file.embedded.to_string()),
+            AssetMode::Runtime(dir) => {
+                let path = dir.join(file.name);
+                std::fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))
+            }
+        }
+    }
+
+    /// Replaces the source of the shader named `name` until set back to `None`. Takes effect on the
+    /// next [`Self::reload_shaders`].
+    pub fn set_shader_override(&mut self, name: &str, source: Option<String>) {
+        match source {
+            Some(source) => self.shader_overrides.insert(name.to_string(), source),
+            None => self.shader_overrides.remove(name),
+        };
+    }
+
+    /// Compiles one GLSL stage from `assets/<name>` and reflects it. Errors come back as a rendered report.
+    pub(crate) fn load_stage(
+        &mut self,
+        file: &ShaderFile,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<ShaderStageModule, String> {
+        let path = format!("assets/{}", file.name);
+        let source = self.shader_source(file)?;
+        let stage = file.stage;
+        let from = SourceLang::Glsl(stage);
+        let (module, parsed) = self
+            .shader_cache
+            .parsed_module(&self.device, Some(file.name), &source, from, defines)
+            .map_err(|e| e.emit_to_string(&path))?;
+        let reflection =
+            ShaderReflection::new(&parsed.module, &parsed.info, stage, None).map_err(|e| format!("{path}: {e}"))?;
+        Ok(ShaderStageModule { module, reflection })
+    }
+
+    /// Runs `f` inside a wgpu validation error scope, so a bad shader reports instead of panicking.
+    pub(crate) fn validated<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        // 捕获 wgpu 校验错误,避免着色器热重载时直接 panic
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = f(self);
+        match (result, pop_error_scope_now(&self.device)) {
+            (Ok(value), None) => Ok(value),
+            (Ok(_), Some(error)) => Err(error.to_string()),
+            (Err(report), _) => Err(report),
+        }
+    }
+
+    fn create_pentagon_pass(&mut self) -> Result<MeshPass, String> {
+        let render_pipeline = self.create_pentagon_pipeline()?;
+
+        let vertex_buffer = self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: crate::utils::cast_slice(VERTICES),
+                usage: wgpu::BufferUsages::VERTEX,
+            }
+        );
+
+        let index_buffer = self.device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Index Buffer"),
+                contents: crate::utils::cast_slice(INDICES),
+                usage: wgpu::BufferUsages::INDEX,
+            }
+        );
+
+        let num_indices = INDICES.len() as u32;
+
+        Ok(MeshPass {
+            pipeline: render_pipeline,
+            vertex_buffer,
+            index_buffer,
+            index_count: num_indices,
+        })
+    }
+
+    /// Builds the pentagon pipeline with its layout taken from shader reflection.
+    fn create_pentagon_pipeline(&mut self) -> Result<wgpu::RenderPipeline, String> {
+        self.validated(|this| {
+            let defines = BTreeMap::new();
+            let vs = this.load_stage(&PENTAGON_SHADERS[0], &defines);
+            let fs = this.load_stage(&PENTAGON_SHADERS[1], &defines);
+            let (vs, fs) = match (vs, fs) {
+                (Ok(vs), Ok(fs)) => (vs, fs),
+                (vs, fs) => {
+                    let reports: Vec<String> = [vs.err(), fs.err()].into_iter().flatten().collect();
+                    return Err(reports.join("\n"));
+                }
+            };
+            // `Vertex` must match what the vertex shader reads
+            check_vertex_buffers(&vs.reflection, &[Vertex::layout()]).map_err(|e| format!("assets/shader.vert: {e}"))?;
+            let layout = PipelineReflection::new([&vs.reflection, &fs.reflection]).map_err(|e| e.to_string())?;
+            let render_pipeline_layout = layout.create_pipeline_layout(
+                &this.device,
+                &layout.create_bind_group_layouts(&this.device, Some("Forward Bind Group Layout")),
+                Some("Render Pipeline Layout"),
+            );
+
+            Ok(this.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &vs.module,
+                    entry_point: "main",
+                    compilation_options: Default::default(),
+                    buffers: &[Vertex::layout()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &fs.module,
+                    compilation_options: Default::default(),
+                    entry_point: "main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: this.format,
+                        blend: Some(wgpu::BlendState {
+                            color: wgpu::BlendComponent::REPLACE,
+                            alpha: wgpu::BlendComponent::REPLACE,
+                        }),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    // Requires Features::DEPTH_CLIP_CONTROL
+                    unclipped_depth: false,
+                    // Requires Features::CONSERVATIVE_RASTERIZATION
+                    conservative: false,
+                },
+                depth_stencil: None, // 1.
+                multisample: wgpu::MultisampleState {
+                    count: this.sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None, // 5.
+            }))
+        })
+    }
+}
+
+fn render_pentagon(pass: &MeshPass, encoder: &mut wgpu::CommandEncoder, target: ColorTarget) {
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[
+            // 这就是片元着色器中 @location(0) 标记指向的颜色附件
+            Some(target.attachment(wgpu::LoadOp::Clear(CLEAR_COLOR)))
+        ],
+        ..Default::default()
+    });
+
+    render_pass.set_pipeline(&pass.pipeline); // 2.
+    render_pass.set_vertex_buffer(0, pass.vertex_buffer.slice(..)); // 3.
+    render_pass.set_index_buffer(pass.index_buffer.slice(..), wgpu::IndexFormat::Uint16); // 4.
+    render_pass.draw_indexed(0..pass.index_count, 0, 0..1);
+}
+
+fn pop_error_scope_now(device: &wgpu::Device) -> Option<wgpu::Error> {
+    block_on(device.pop_error_scope())
+}
+
+/// Runs `future` to completion on this thread, parking it until the future's waker fires.
+/// The event loop already runs inside the tokio runtime, which cannot be re-entered.
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct ThreadWaker(std::thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut context = Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+fn watch_device_lost(device: &wgpu::Device) -> Arc<AtomicBool> {
+    let lost = Arc::new(AtomicBool::new(false));
+    let flag = lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        // Dropped 和 ReplacedCallback 是正常关闭,不算丢失
+        if !matches!(reason, wgpu::DeviceLostReason::Dropped | wgpu::DeviceLostReason::ReplacedCallback) {
+            eprintln!("device lost ({reason:?}): {message}");
+            flag.store(true, Ordering::Release);
+        }
+    });
+    lost
+}
@@ -0,0 +1,68 @@
+use std::ops::Range;
+
+use cgmath::{Deg, EuclideanSpace, Matrix4, Point3, Vector3};
+
+use crate::renderer::Scene;
+use crate::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::shadow::MAX_LIGHTS;
+
+/// Built-in meshes from [`crate::vertex`]. Each is uploaded once and shared by every entity using it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshKind {
+    /// [`crate::vertex::create_cube`], spanning -1..1 on every axis.
+    Cube,
+    /// [`crate::vertex::create_plane`], spanning -1..1 in XZ and facing +Y.
+    Plane,
+}
+
+/// Handle to a spawned entity. Ids are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub(crate) u32);
+
+/// Everything needed to spawn an entity, and what [`crate::renderer::Renderer::update_entity`] edits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityDesc {
+    pub mesh: MeshKind,
+    pub mx_world: cgmath::Matrix4<f32>,
+    /// Spin around the entity's local Y axis, in radians per second.
+    pub rotation_speed: f32,
+    pub color: wgpu::Color,
+}
+
+/// Handle to a light. Ids are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LightId(pub(crate) u32);
+
+/// A shadow-casting spot light looking at the origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightDesc {
+    pub pos: Point3<f32>,
+    pub color: wgpu::Color,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    /// Near and far plane of the shadow map.
+    pub depth: Range<f32>,
+}
+
+impl LightDesc {
+    /// View-projection used both to render this light's shadow map and to sample it.
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        let view = Matrix4::look_at_rh(self.pos, Point3::origin(), Vector3::unit_y());
+        let projection = cgmath::perspective(Deg(self.fov), 1.0, self.depth.start, self.depth.end);
+        OPENGL_TO_WGPU_MATRIX * projection * view
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum SceneError {
+    #[error("the {} scene has no entities or lights", .0.name())]
+    Unsupported(Scene),
+    #[error("the scene is not loaded, fix the shader error first")]
+    NotLoaded,
+    #[error("no entity with id {0:?}")]
+    UnknownEntity(EntityId),
+    #[error("no light with id {0:?}")]
+    UnknownLight(LightId),
+    #[error("the shaders support at most {MAX_LIGHTS} lights")]
+    TooManyLights,
+}
@@ -0,0 +1,386 @@
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::utils::{
+    expand_includes, parse_module, write_module, ShaderOutput, ShaderTranslateError, SourceLang, TargetLang,
+    TranslateOptions,
+};
+
+/// Identifies one shader variant. The stage is part of `from` for GLSL sources.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShaderKey {
+    pub source_hash: u64,
+    pub from: SourceLang,
+    pub defines: BTreeMap<String, String>,
+    pub target: TargetLang,
+}
+
+impl ShaderKey {
+    pub fn new(source_hash: u64, from: SourceLang, defines: &BTreeMap<String, String>, target: TargetLang) -> Self {
+        Self {
+            source_hash,
+            from,
+            defines: defines.clone(),
+            target,
+        }
+    }
+
+    /// `options_hash` keeps caches built with different backend options apart on disk.
+    fn file_name(&self, options_hash: u64) -> String {
+        let extension = match self.target {
+            TargetLang::Wgsl => "wgsl",
+            TargetLang::Msl => "metal",
+            TargetLang::Hlsl => "hlsl",
+            TargetLang::Glsl => "glsl",
+            TargetLang::SpirV => "spv",
+        };
+        format!("{:016x}.{extension}", hash_of((env!("CARGO_PKG_VERSION"), options_hash, self)))
+    }
+}
+
+/// The naga IR a cached module was built from, kept so callers can reflect it without
+/// parsing the source again.
+#[derive(Debug)]
+pub struct ParsedShader {
+    pub module: naga::Module,
+    pub info: naga::valid::ModuleInfo,
+}
+
+fn hash_of(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Every option that changes the translated output. Included files are not covered here,
+/// their contents go into the source hash, see `ShaderCache::source_hash`.
+fn options_hash(options: &TranslateOptions) -> u64 {
+    let TranslateOptions {
+        capabilities,
+        preprocess,
+        msl,
+        hlsl,
+        glsl,
+        spirv,
+    } = options;
+    hash_of((
+        capabilities.bits(),
+        (&preprocess.defines, preprocess.include_resolver.is_some()),
+        (msl.lang_version, msl.fake_missing_bindings),
+        (hlsl.shader_model.to_str(), hlsl.fake_missing_bindings),
+        (glsl.version.to_string(), &glsl.entry_point, glsl.writer_flags.bits()),
+        (spirv.lang_version, spirv.flags.bits()),
+    ))
+}
+
+/// How many variants [`ShaderCache`] keeps in memory unless told otherwise.
+pub const DEFAULT_MAX_ENTRIES: usize = 64;
+
+/// Memoizes shader translation and `wgpu::ShaderModule` creation per variant.
+///
+/// Backend settings come from the `TranslateOptions` the cache is built with; only the
+/// defines vary per lookup. Modules belong to the device they were created on, so call
+/// [`ShaderCache::clear_modules`] when the device is replaced.
+///
+/// Every edited source is a new variant, so hot reloads would grow the cache forever. Past
+/// `max_entries` variants the least recently used one is dropped from memory.
+#[derive(Debug)]
+pub struct ShaderCache {
+    options: TranslateOptions,
+    options_hash: u64,
+    disk_dir: Option<PathBuf>,
+    outputs: HashMap<ShaderKey, Arc<ShaderOutput>>,
+    parsed: HashMap<ShaderKey, Arc<ParsedShader>>,
+    modules: HashMap<ShaderKey, Arc<wgpu::ShaderModule>>,
+    max_entries: usize,
+    /// When each variant was last looked up, on `clock`.
+    last_used: HashMap<ShaderKey, u64>,
+    clock: u64,
+}
+
+impl Default for ShaderCache {
+    fn default() -> Self {
+        Self::new(TranslateOptions::default())
+    }
+}
+
+impl ShaderCache {
+    pub fn new(options: TranslateOptions) -> Self {
+        Self {
+            options_hash: options_hash(&options),
+            options,
+            disk_dir: None,
+            outputs: HashMap::new(),
+            parsed: HashMap::new(),
+            modules: HashMap::new(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            last_used: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Keeps at most `max_entries` variants in memory, at least one.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Also keep translated sources on disk so a restart does not re-run naga.
+    pub fn with_disk_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk_dir = Some(dir.into());
+        self
+    }
+
+    /// Turns the disk cache on in `dir`, or off with `None`. Off by default.
+    pub fn set_disk_cache(&mut self, dir: Option<PathBuf>) {
+        self.disk_dir = dir;
+    }
+
+    pub fn disk_cache(&self) -> Option<&Path> {
+        self.disk_dir.as_deref()
+    }
+
+    pub fn options(&self) -> &TranslateOptions {
+        &self.options
+    }
+
+    /// Translated source for one variant, from memory, disk or naga in that order.
+    pub fn translate(
+        &mut self,
+        source: impl AsRef<[u8]>,
+        from: SourceLang,
+        to: TargetLang,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<Arc<ShaderOutput>, ShaderTranslateError> {
+        let source = source.as_ref();
+        let key = ShaderKey::new(self.source_hash(source, from, defines)?, from, defines, to);
+        if let Some(output) = self.outputs.get(&key).cloned() {
+            self.touch(&key);
+            return Ok(output);
+        }
+
+        let output = match self.load(&key) {
+            Some(output) => output,
+            None => {
+                let options = self.variant_options(defines);
+                let (module, info) = parse_module(source, from, &options)?;
+                let output = write_module(&module, &info, to, &options)?;
+                self.store(&key, &output);
+                output
+            }
+        };
+        let output = Arc::new(output);
+        self.outputs.insert(key.clone(), output.clone());
+        self.touch(&key);
+        self.evict();
+        Ok(output)
+    }
+
+    /// A shader module for one variant, translated to WGSL on a miss.
+    pub fn module(
+        &mut self,
+        device: &wgpu::Device,
+        label: Option<&str>,
+        source: impl AsRef<[u8]>,
+        from: SourceLang,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<Arc<wgpu::ShaderModule>, ShaderTranslateError> {
+        self.parsed_module(device, label, source, from, defines).map(|(module, _)| module)
+    }
+
+    /// Like [`Self::module`], also returning the IR the module was built from. Each variant is
+    /// parsed once: the source on a miss, the cached WGSL on a disk hit. wgpu gets the IR, so it
+    /// does not parse the WGSL a second time.
+    pub fn parsed_module(
+        &mut self,
+        device: &wgpu::Device,
+        label: Option<&str>,
+        source: impl AsRef<[u8]>,
+        from: SourceLang,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<(Arc<wgpu::ShaderModule>, Arc<ParsedShader>), ShaderTranslateError> {
+        let source = source.as_ref();
+        let key = ShaderKey::new(self.source_hash(source, from, defines)?, from, defines, TargetLang::Wgsl);
+        let parsed = match self.parsed.get(&key) {
+            Some(parsed) => parsed.clone(),
+            None => {
+                let parsed = Arc::new(self.parse(&key, source, defines)?);
+                self.parsed.insert(key.clone(), parsed.clone());
+                parsed
+            }
+        };
+        self.touch(&key);
+        if let Some(module) = self.modules.get(&key) {
+            return Ok((module.clone(), parsed));
+        }
+
+        let module = Arc::new(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label,
+            source: wgpu::ShaderSource::Naga(Cow::Owned(parsed.module.clone())),
+        }));
+        self.modules.insert(key, module.clone());
+        self.evict();
+        Ok((module, parsed))
+    }
+
+    pub fn clear_modules(&mut self) {
+        self.modules.clear();
+    }
+
+    /// Drops every in-memory entry. The disk cache is left alone.
+    pub fn clear(&mut self) {
+        self.outputs.clear();
+        self.parsed.clear();
+        self.modules.clear();
+        self.last_used.clear();
+    }
+
+    /// Number of variants held in memory.
+    pub fn len(&self) -> usize {
+        self.last_used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.last_used.is_empty()
+    }
+
+    fn touch(&mut self, key: &ShaderKey) {
+        self.clock += 1;
+        self.last_used.insert(key.clone(), self.clock);
+    }
+
+    /// Drops the least recently used variants until at most `max_entries` are left.
+    fn evict(&mut self) {
+        while self.last_used.len() > self.max_entries {
+            let Some(oldest) = self.last_used.iter().min_by_key(|(_, &used)| used).map(|(key, _)| key.clone()) else {
+                return;
+            };
+            self.last_used.remove(&oldest);
+            self.outputs.remove(&oldest);
+            self.parsed.remove(&oldest);
+            self.modules.remove(&oldest);
+        }
+    }
+
+    /// IR for a WGSL variant: parsed from the translation if there already is one, otherwise
+    /// from `source`, storing the translation on the way.
+    fn parse(
+        &mut self,
+        key: &ShaderKey,
+        source: &[u8],
+        defines: &BTreeMap<String, String>,
+    ) -> Result<ParsedShader, ShaderTranslateError> {
+        let cached = self.outputs.get(key).cloned().or_else(|| self.load(key).map(Arc::new));
+        if let Some(output) = cached {
+            let wgsl = output.as_text().unwrap_or_default();
+            let (module, info) = parse_module(wgsl, SourceLang::Wgsl, &self.options)?;
+            self.outputs.insert(key.clone(), output);
+            return Ok(ParsedShader { module, info });
+        }
+
+        let options = self.variant_options(defines);
+        let (module, info) = parse_module(source, key.from, &options)?;
+        let output = write_module(&module, &info, TargetLang::Wgsl, &options)?;
+        self.store(key, &output);
+        self.outputs.insert(key.clone(), Arc::new(output));
+        Ok(ParsedShader { module, info })
+    }
+
+    fn variant_options(&self, defines: &BTreeMap<String, String>) -> TranslateOptions {
+        let mut options = self.options.clone();
+        options.preprocess.defines.extend(defines.clone());
+        options
+    }
+
+    /// Hashes GLSL after `#include` expansion so edits to included files miss the cache. Which
+    /// files get included can depend on the defines, so they are applied first.
+    fn source_hash(
+        &self,
+        source: &[u8],
+        from: SourceLang,
+        defines: &BTreeMap<String, String>,
+    ) -> Result<u64, ShaderTranslateError> {
+        let resolves_includes = self.options.preprocess.include_resolver.is_some();
+        match (from, std::str::from_utf8(source)) {
+            (SourceLang::Glsl(_), Ok(text)) if resolves_includes => {
+                let (expanded, _) = expand_includes(text, &self.variant_options(defines).preprocess)?;
+                Ok(hash_of(expanded))
+            }
+            _ => Ok(hash_of(source)),
+        }
+    }
+
+    fn load(&self, key: &ShaderKey) -> Option<ShaderOutput> {
+        let bytes = std::fs::read(self.disk_dir.as_ref()?.join(key.file_name(self.options_hash))).ok()?;
+        match key.target {
+            TargetLang::SpirV => Some(ShaderOutput::SpirV(
+                bytes
+                    .chunks_exact(4)
+                    .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+                    .collect(),
+            )),
+            _ => String::from_utf8(bytes).ok().map(ShaderOutput::Text),
+        }
+    }
+
+    fn store(&self, key: &ShaderKey, output: &ShaderOutput) {
+        let Some(dir) = &self.disk_dir else { return };
+        // A failed write only costs a re-translation next launch.
+        if std::fs::create_dir_all(dir).is_ok() {
+            let _ = std::fs::write(dir.join(key.file_name(self.options_hash)), output.to_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WGSL: &str = "@vertex fn main() -> @builtin(position) vec4<f32> { return vec4<f32>(VALUE); }";
+
+    fn translate(cache: &mut ShaderCache, value: &str) -> Arc<ShaderOutput> {
+        let source = WGSL.replace("VALUE", value);
+        cache.translate(source, SourceLang::Wgsl, TargetLang::Wgsl, &BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn reuses_translations() {
+        let mut cache = ShaderCache::default();
+        let first = translate(&mut cache, "0.0");
+        assert!(Arc::ptr_eq(&first, &translate(&mut cache, "0.0")));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_variant() {
+        let mut cache = ShaderCache::default().with_max_entries(2);
+        let first = translate(&mut cache, "0.0");
+        translate(&mut cache, "1.0");
+        // 再用一次第一个,被淘汰的应该是第二个
+        translate(&mut cache, "0.0");
+        translate(&mut cache, "2.0");
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(&first, &translate(&mut cache, "0.0")));
+        assert_eq!(cache.len(), 2);
+
+        // 每次编辑都是新的变体,缓存大小不再增长
+        for i in 3..20 {
+            translate(&mut cache, &format!("{i}.0"));
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.outputs.len(), 2);
+    }
+
+    #[test]
+    fn options_hash_follows_the_options() {
+        let options = TranslateOptions::default();
+        let mut other = options.clone();
+        assert_eq!(options_hash(&options), options_hash(&other));
+        other.msl.lang_version = (2, 4);
+        assert_ne!(options_hash(&options), options_hash(&other));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::renderer::{Renderer, ShaderFile};
+use crate::utils::{parse_module, write_module, ShaderTranslateError, SourceLang, TargetLang, TranslateOptions};
+
+/// Languages the output pane can show.
+const TARGETS: [(TargetLang, &str); 3] = [(TargetLang::Wgsl, "WGSL"), (TargetLang::Msl, "MSL"), (TargetLang::Hlsl, "HLSL")];
+
+/// One naga message, flattened for the diagnostics list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based, `None` when naga gave no span.
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+/// What the user asked for in the editor this frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorAction {
+    /// Compile the pipelines from these `(name, source)` pairs.
+    Apply(Vec<(&'static str, String)>),
+    /// Drop the edits and go back to the shader files.
+    Reset,
+}
+
+#[derive(Debug)]
+struct EditedShader {
+    file: ShaderFile,
+    source: String,
+    /// Translation of `source` into the selected target, or why it failed.
+    output: Result<String, Vec<Diagnostic>>,
+}
+
+/// Shader playground: edit the scene's GLSL, watch the translation update as you type and
+/// apply it to the running pipelines. Toggled with F2.
+#[derive(Debug)]
+pub struct ShaderEditor {
+    pub open: bool,
+    shaders: Vec<EditedShader>,
+    selected: usize,
+    target: TargetLang,
+    options: TranslateOptions,
+}
+
+impl Default for ShaderEditor {
+    fn default() -> Self {
+        Self {
+            open: false,
+            shaders: Vec::new(),
+            selected: 0,
+            target: TargetLang::Wgsl,
+            options: TranslateOptions::default(),
+        }
+    }
+}
+
+impl ShaderEditor {
+    /// Opens the editor, loading the scene's current sources the first time.
+    pub fn toggle(&mut self, renderer: &Renderer) {
+        self.open = !self.open;
+        if self.open {
+            self.load_once(renderer);
+        }
+    }
+
+    /// Loads the scene's sources unless there already are buffers to edit.
+    pub fn load_once(&mut self, renderer: &Renderer) {
+        if self.shaders.is_empty() {
+            self.load(renderer);
+        }
+    }
+
+    /// Replaces the buffers with what the renderer currently compiles, dropping unapplied edits.
+    pub fn load(&mut self, renderer: &Renderer) {
+        self.options = renderer.translate_options();
+        self.shaders = renderer
+            .shader_files()
+            .iter()
+            .map(|file| {
+                let source = renderer.shader_source(file).unwrap_or_else(|e| format!("// {e}"));
+                let mut shader = EditedShader {
+                    file: *file,
+                    source,
+                    output: Ok(String::new()),
+                };
+                shader.output = translate(&shader, self.target, &self.options);
+                shader
+            })
+            .collect();
+        self.selected = self.selected.min(self.shaders.len().saturating_sub(1));
+    }
+
+    /// Draws the editor as a window over the scene and returns what was clicked, if anything.
+    pub fn ui(&mut self, ctx: &egui::Context) -> Option<EditorAction> {
+        if !self.open {
+            return None;
+        }
+        let mut clicked = (false, false);
+        let mut open = self.open;
+        egui::Window::new("Shader editor")
+            .open(&mut open)
+            .default_size([900.0, 600.0])
+            .show(ctx, |ui| clicked = self.contents(ui));
+        self.open = open;
+        self.action(clicked)
+    }
+
+    /// The same editor filling a window of its own.
+    pub fn panel_ui(&mut self, ctx: &egui::Context) -> Option<EditorAction> {
+        let mut clicked = (false, false);
+        egui::CentralPanel::default().show(ctx, |ui| clicked = self.contents(ui));
+        self.action(clicked)
+    }
+
+    /// Returns whether Apply and Reset were clicked.
+    fn contents(&mut self, ui: &mut egui::Ui) -> (bool, bool) {
+        let mut apply = false;
+        let mut reset = false;
+        ui.horizontal(|ui| {
+            for (i, shader) in self.shaders.iter().enumerate() {
+                let label = match shader.output {
+                    Ok(_) => shader.file.name.to_string(),
+                    Err(_) => format!("{} ⚠", shader.file.name),
+                };
+                ui.selectable_value(&mut self.selected, i, label);
+            }
+            ui.separator();
+            let mut target = self.target;
+            for (lang, name) in TARGETS {
+                ui.selectable_value(&mut target, lang, name);
+            }
+            if target != self.target {
+                self.target = target;
+                for shader in &mut self.shaders {
+                    shader.output = translate(shader, self.target, &self.options);
+                }
+            }
+            ui.separator();
+            let all_ok = self.shaders.iter().all(|shader| shader.output.is_ok());
+            apply = ui.add_enabled(all_ok, egui::Button::new("Apply")).clicked();
+            reset = ui.button("Reset").on_hover_text("Go back to the shader files").clicked();
+        });
+        ui.separator();
+
+        let Some(shader) = self.shaders.get_mut(self.selected) else {
+            ui.label("This scene has no shaders");
+            return (apply, reset);
+        };
+        ui.columns(2, |columns| {
+            egui::ScrollArea::vertical().id_source("glsl").show(&mut columns[0], |ui| {
+                let edit = egui::TextEdit::multiline(&mut shader.source)
+                    .code_editor()
+                    .desired_width(f32::INFINITY)
+                    .desired_rows(30);
+                if ui.add(edit).changed() {
+                    shader.output = translate(shader, self.target, &self.options);
+                }
+            });
+            egui::ScrollArea::vertical().id_source("output").show(&mut columns[1], |ui| match &shader.output {
+                Ok(output) => {
+                    ui.add(egui::Label::new(egui::RichText::new(output).monospace()).selectable(true));
+                }
+                Err(diagnostics) => {
+                    for diagnostic in diagnostics {
+                        let at = match (diagnostic.line, diagnostic.column) {
+                            (Some(line), Some(column)) => format!("{line}:{column}: "),
+                            _ => String::new(),
+                        };
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            egui::RichText::new(format!("{at}{}", diagnostic.message)).monospace(),
+                        );
+                    }
+                }
+            });
+        });
+        (apply, reset)
+    }
+
+    fn action(&self, (apply, reset): (bool, bool)) -> Option<EditorAction> {
+        if reset {
+            return Some(EditorAction::Reset);
+        }
+        apply.then(|| EditorAction::Apply(self.shaders.iter().map(|shader| (shader.file.name, shader.source.clone())).collect()))
+    }
+}
+
+fn translate(shader: &EditedShader, target: TargetLang, options: &TranslateOptions) -> Result<String, Vec<Diagnostic>> {
+    let (module, info) = parse_module(&shader.source, SourceLang::Glsl(shader.file.stage), options)
+        .map_err(|e| diagnostics(&e))?;
+    let output = write_module(&module, &info, target, options).map_err(|e| diagnostics(&e))?;
+    Ok(output.into_text().unwrap_or_default())
+}
+
+fn diagnostics(error: &ShaderTranslateError) -> Vec<Diagnostic> {
+    if error.diagnostics().is_empty() {
+        return vec![Diagnostic {
+            line: None,
+            column: None,
+            message: error.to_string(),
+        }];
+    }
+    error
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| {
+            let label = diagnostic.labels.first();
+            let mut message = diagnostic.message.clone();
+            if let Some(file) = label.and_then(|label| label.file.as_ref()) {
+                message = format!("{file}: {message}");
+            }
+            Diagnostic {
+                line: label.map(|label| label.location.line_number),
+                column: label.map(|label| label.location.line_position),
+                message,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,746 @@
+use std::collections::{BTreeMap, HashMap};
+use std::mem::size_of;
+use std::rc::Rc;
+
+use cgmath::{Deg, Matrix4, Point3, Rad, Vector3};
+use wgpu::util::DeviceExt;
+
+use crate::camera::Camera;
+use crate::depth::{DepthBuffer, DepthTest};
+use crate::msaa::ColorTarget;
+use crate::data_stuct::{CubeDesc, Entity, Light, Mesh, Pass, State};
+use crate::reflection::PipelineReflection;
+use crate::renderer::{Renderer, ShaderFile, CLEAR_COLOR};
+use crate::scene::{EntityDesc, EntityId, LightDesc, LightId, MeshKind, SceneError};
+use crate::utils::cast_slice;
+use crate::vertex::{create_cube, create_plane, Vertex};
+use crate::vertex_layout::{check_vertex_buffers, VertexLayout};
+
+/// Size of the `Lights` uniform array, passed to the shaders as the `MAX_LIGHTS` define.
+pub const MAX_LIGHTS: usize = 10;
+const SHADOW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const SHADOW_SIZE: wgpu::Extent3d = wgpu::Extent3d {
+    width: 512,
+    height: 512,
+    depth_or_array_layers: MAX_LIGHTS as u32,
+};
+
+/// The shadow bake vertex shader, then the forward vertex and fragment shaders.
+pub(crate) const SHADERS: [ShaderFile; 3] = [
+    ShaderFile {
+        name: "glsl-in/bake.vert",
+        embedded: include_str!("../assets/glsl-in/bake.vert"),
+        stage: naga::ShaderStage::Vertex,
+    },
+    ShaderFile {
+        name: "glsl-in/shader.vert",
+        embedded: include_str!("../assets/glsl-in/shader.vert"),
+        stage: naga::ShaderStage::Vertex,
+    },
+    ShaderFile {
+        name: "glsl-in/shader.frag",
+        embedded: include_str!("../assets/glsl-in/shader.frag"),
+        stage: naga::ShaderStage::Fragment,
+    },
+];
+
+pub(crate) fn defines() -> BTreeMap<String, String> {
+    BTreeMap::from([("MAX_LIGHTS".to_string(), MAX_LIGHTS.to_string())])
+}
+
+/// `Globals` in the forward shaders.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct GlobalsRaw {
+    view_proj: [[f32; 4]; 4],
+    num_lights: [u32; 4],
+}
+
+/// One element of `u_Lights`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LightRaw {
+    proj: [[f32; 4]; 4],
+    pos: [f32; 4],
+    color: [f32; 4],
+}
+
+/// `Entity` in both the bake and forward shaders.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct EntityRaw {
+    world: [[f32; 4]; 4],
+    color: [f32; 4],
+}
+
+impl Light {
+    pub(crate) fn desc(&self) -> LightDesc {
+        LightDesc {
+            pos: self.pos,
+            color: self.color,
+            fov: self.fov,
+            depth: self.depth.clone(),
+        }
+    }
+
+    fn to_raw(&self) -> LightRaw {
+        LightRaw {
+            proj: self.desc().view_projection().into(),
+            pos: [self.pos.x, self.pos.y, self.pos.z, 1.0],
+            color: [self.color.r as f32, self.color.g as f32, self.color.b as f32, 1.0],
+        }
+    }
+}
+
+impl Entity {
+    pub(crate) fn desc(&self) -> EntityDesc {
+        EntityDesc {
+            mesh: self.mesh,
+            mx_world: self.mx_world,
+            rotation_speed: self.rotation_speed,
+            color: self.color,
+        }
+    }
+
+    /// `alpha` goes from the previous update step (0) to the latest one (1).
+    fn to_raw(&self, alpha: f32) -> EntityRaw {
+        let world = match alpha {
+            a if a >= 1.0 => self.mx_world,
+            a => self.prev_world * (1.0 - a) + self.mx_world * a,
+        };
+        EntityRaw {
+            world: world.into(),
+            color: [self.color.r as f32, self.color.g as f32, self.color.b as f32, self.color.a as f32],
+        }
+    }
+}
+
+/// The pipelines of both passes, built from `assets/glsl-in`.
+struct Pipelines {
+    shadow: wgpu::RenderPipeline,
+    shadow_reflection: PipelineReflection,
+    forward: wgpu::RenderPipeline,
+    forward_reflection: PipelineReflection,
+}
+
+fn create_pipelines(renderer: &mut Renderer) -> Result<Pipelines, String> {
+    let defines = defines();
+    let format = renderer.format();
+    let depth_test = renderer.depth_test();
+    let sample_count = renderer.sample_count();
+    renderer.validated(|renderer| {
+        let [bake, vs, fs] = SHADERS.map(|file| renderer.load_stage(&file, &defines));
+        let (bake, vs, fs) = match (bake, vs, fs) {
+            (Ok(bake), Ok(vs), Ok(fs)) => (bake, vs, fs),
+            (bake, vs, fs) => {
+                let reports: Vec<String> = [bake.err(), vs.err(), fs.err()].into_iter().flatten().collect();
+                return Err(reports.join("\n"));
+            }
+        };
+        check_vertex_buffers(&bake.reflection, &[Vertex::layout()]).map_err(|e| format!("assets/glsl-in/bake.vert: {e}"))?;
+        check_vertex_buffers(&vs.reflection, &[Vertex::layout()]).map_err(|e| format!("assets/glsl-in/shader.vert: {e}"))?;
+
+        let forward_reflection = PipelineReflection::new([&vs.reflection, &fs.reflection]).map_err(|e| e.to_string())?;
+        // Both passes bind the same per-entity bind group at set 1.
+        let mut shadow_reflection = PipelineReflection::new([&bake.reflection]).map_err(|e| e.to_string())?;
+        shadow_reflection
+            .adopt_group(1, &forward_reflection)
+            .map_err(|e| format!("assets/glsl-in/bake.vert: {e}"))?;
+
+        let device = renderer.device();
+        let primitive = wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            ..Default::default()
+        };
+
+        let shadow_layout = shadow_reflection.create_pipeline_layout(
+            device,
+            &shadow_reflection.create_bind_group_layouts(device, Some("Shadow Bind Group Layout")),
+            Some("Shadow Pipeline Layout"),
+        );
+        let shadow = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&shadow_layout),
+            vertex: wgpu::VertexState {
+                module: &bake.module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                buffers: &[Vertex::layout()],
+            },
+            fragment: None,
+            primitive,
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: SHADOW_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                // Pushes the stored depth back a little so lit surfaces don't shadow themselves.
+                bias: wgpu::DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let forward_layout = forward_reflection.create_pipeline_layout(
+            device,
+            &forward_reflection.create_bind_group_layouts(device, Some("Forward Bind Group Layout")),
+            Some("Forward Pipeline Layout"),
+        );
+        let forward = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Forward Pipeline"),
+            layout: Some(&forward_layout),
+            vertex: wgpu::VertexState {
+                module: &vs.module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                buffers: &[Vertex::layout()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fs.module,
+                entry_point: "main",
+                compilation_options: Default::default(),
+                targets: &[Some(format.into())],
+            }),
+            primitive,
+            depth_stencil: depth_test.as_ref().map(DepthTest::state),
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        Ok(Pipelines {
+            shadow,
+            shadow_reflection,
+            forward,
+            forward_reflection,
+        })
+    })
+}
+
+/// Swaps in freshly compiled pipelines, as long as their bindings did not change.
+pub(crate) fn reload_pipelines(renderer: &mut Renderer, state: &mut State) -> Result<(), String> {
+    let pipelines = create_pipelines(renderer)?;
+    if pipelines.shadow_reflection.bind_groups != state.shadow_pass.reflection.bind_groups
+        || pipelines.forward_reflection.bind_groups != state.forward_pass.reflection.bind_groups
+    {
+        return Err("the shader bindings changed, restart to pick up the new layout".to_string());
+    }
+    state.shadow_pass.pipeline = pipelines.shadow;
+    state.forward_pass.pipeline = pipelines.forward;
+    // The depth test may have been switched along with the pipelines
+    state.forward_depth = forward_depth(renderer, state.forward_depth.take());
+    Ok(())
+}
+
+/// The cube-and-plane scene the shadow shaders were written for.
+pub(crate) fn create_state(renderer: &mut Renderer) -> Result<State, String> {
+    let pipelines = create_pipelines(renderer)?;
+    let device = renderer.device();
+
+    let entity_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Entity Bind Group Layout"),
+        entries: &pipelines.forward_reflection.bind_group_layout_entries(1),
+    });
+    // One layer of the shadow texture per light.
+    let shadow_texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Shadow Texture"),
+        size: SHADOW_SIZE,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: SHADOW_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        view_formats: &[],
+    });
+    let shadow_view = shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+    let light_uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Light Uniform Buffer"),
+        size: (MAX_LIGHTS * size_of::<LightRaw>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let shadow_pass = {
+        // Only the light's view-projection; it is copied in from the light buffer before each light's pass.
+        let uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Uniform Buffer"),
+            size: size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Shadow Bind Group"),
+            layout: &pipelines.shadow.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buf.as_entire_binding(),
+            }],
+        });
+        Pass {
+            pipeline: pipelines.shadow,
+            bind_group,
+            uniform_buf,
+            reflection: pipelines.shadow_reflection,
+        }
+    };
+
+    let forward_pass = {
+        let uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Globals Uniform Buffer"),
+            size: size_of::<GlobalsRaw>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Forward Bind Group"),
+            layout: &pipelines.forward.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: light_uniform_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&shadow_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&shadow_sampler),
+                },
+            ],
+        });
+        Pass {
+            pipeline: pipelines.forward,
+            bind_group,
+            uniform_buf,
+            reflection: pipelines.forward_reflection,
+        }
+    };
+
+    let mut state = State {
+        lights: Vec::new(),
+        lights_are_dirty: true,
+        light_uniform_buf,
+        next_light_id: 0,
+        shadow_texture,
+        entities: Vec::new(),
+        next_entity_id: 0,
+        meshes: HashMap::new(),
+        entity_bind_group_layout,
+        shadow_pass,
+        forward_depth: forward_depth(renderer, None),
+        forward_pass,
+    };
+    let light_descs = [
+        LightDesc {
+            pos: Point3::new(7.0, 10.0, -5.0),
+            color: wgpu::Color {
+                r: 0.5,
+                g: 1.0,
+                b: 0.5,
+                a: 1.0,
+            },
+            fov: 60.0,
+            depth: 1.0..20.0,
+        },
+        LightDesc {
+            pos: Point3::new(-5.0, 10.0, 7.0),
+            color: wgpu::Color {
+                r: 1.0,
+                g: 0.5,
+                b: 0.5,
+                a: 1.0,
+            },
+            fov: 45.0,
+            depth: 1.0..20.0,
+        },
+    ];
+    for desc in light_descs {
+        add_light(&mut state, desc).map_err(|err| err.to_string())?;
+    }
+
+    let cube_descs = [
+        CubeDesc {
+            offset: Vector3::new(-2.0, 0.7, -2.0),
+            angle: 10.0,
+            scale: 0.7,
+            rotation: 0.1,
+        },
+        CubeDesc {
+            offset: Vector3::new(2.0, 1.3, -2.0),
+            angle: 50.0,
+            scale: 1.3,
+            rotation: 0.2,
+        },
+        CubeDesc {
+            offset: Vector3::new(-2.0, 1.1, 2.0),
+            angle: 140.0,
+            scale: 1.1,
+            rotation: 0.3,
+        },
+        CubeDesc {
+            offset: Vector3::new(2.0, 0.9, 2.0),
+            angle: 210.0,
+            scale: 0.9,
+            rotation: 0.4,
+        },
+    ];
+    spawn_entity(
+        &mut state,
+        device,
+        EntityDesc {
+            mesh: MeshKind::Plane,
+            mx_world: Matrix4::from_scale(7.0),
+            rotation_speed: 0.0,
+            color: wgpu::Color::WHITE,
+        },
+    );
+    for desc in &cube_descs {
+        let mx_world = Matrix4::from_translation(desc.offset)
+            * Matrix4::from_angle_y(Deg(desc.angle))
+            * Matrix4::from_scale(desc.scale);
+        spawn_entity(
+            &mut state,
+            device,
+            EntityDesc {
+                mesh: MeshKind::Cube,
+                mx_world,
+                rotation_speed: desc.rotation,
+                color: wgpu::Color::GREEN,
+            },
+        );
+    }
+    Ok(state)
+}
+
+/// Uploads the mesh on first use, later calls share the same buffers.
+fn mesh(state: &mut State, device: &wgpu::Device, kind: MeshKind) -> Mesh {
+    state
+        .meshes
+        .entry(kind)
+        .or_insert_with(|| {
+            let (vertices, indices): (Vec<Vertex>, Vec<u16>) = match kind {
+                MeshKind::Cube => create_cube(),
+                MeshKind::Plane => create_plane(),
+            };
+            let vertex_buf = Rc::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Vertex Buffer"),
+                contents: cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            }));
+            let index_buf = Rc::new(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Index Buffer"),
+                contents: cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            }));
+            Mesh {
+                vertex_buf,
+                index_buf,
+                index_count: indices.len(),
+            }
+        })
+        .clone()
+}
+
+/// Each entity gets its own small uniform buffer; it is written on the next frame.
+pub(crate) fn spawn_entity(state: &mut State, device: &wgpu::Device, desc: EntityDesc) -> EntityId {
+    let Mesh {
+        vertex_buf,
+        index_buf,
+        index_count,
+    } = mesh(state, device, desc.mesh);
+    let uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Entity Uniform Buffer"),
+        size: size_of::<EntityRaw>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Entity Bind Group"),
+        layout: &state.entity_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: uniform_buf.as_entire_binding(),
+        }],
+    });
+    let id = EntityId(state.next_entity_id);
+    state.next_entity_id += 1;
+    state.entities.push(Entity {
+        id,
+        mesh: desc.mesh,
+        mx_world: desc.mx_world,
+        prev_world: desc.mx_world,
+        rotation_speed: desc.rotation_speed,
+        color: desc.color,
+        vertex_buf,
+        index_buf,
+        index_count,
+        bind_group,
+        uniform_buf,
+        uniform_dirty: true,
+    });
+    id
+}
+
+pub(crate) fn despawn_entity(state: &mut State, id: EntityId) -> Result<(), SceneError> {
+    let index = state
+        .entities
+        .iter()
+        .position(|entity| entity.id == id)
+        .ok_or(SceneError::UnknownEntity(id))?;
+    state.entities.remove(index);
+    Ok(())
+}
+
+pub(crate) fn update_entity(
+    state: &mut State,
+    device: &wgpu::Device,
+    id: EntityId,
+    f: impl FnOnce(&mut EntityDesc),
+) -> Result<(), SceneError> {
+    let index = state
+        .entities
+        .iter()
+        .position(|entity| entity.id == id)
+        .ok_or(SceneError::UnknownEntity(id))?;
+    let mut desc = state.entities[index].desc();
+    f(&mut desc);
+    if desc.mesh != state.entities[index].mesh {
+        let mesh = mesh(state, device, desc.mesh);
+        let entity = &mut state.entities[index];
+        entity.mesh = desc.mesh;
+        entity.vertex_buf = mesh.vertex_buf;
+        entity.index_buf = mesh.index_buf;
+        entity.index_count = mesh.index_count;
+    }
+    let entity = &mut state.entities[index];
+    // 直接设置的位置不做插值
+    entity.mx_world = desc.mx_world;
+    entity.prev_world = desc.mx_world;
+    entity.rotation_speed = desc.rotation_speed;
+    entity.color = desc.color;
+    entity.uniform_dirty = true;
+    Ok(())
+}
+
+fn light_target_view(shadow_texture: &wgpu::Texture, layer: usize) -> wgpu::TextureView {
+    shadow_texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Shadow Target View"),
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_array_layer: layer as u32,
+        array_layer_count: Some(1),
+        ..Default::default()
+    })
+}
+
+pub(crate) fn add_light(state: &mut State, desc: LightDesc) -> Result<LightId, SceneError> {
+    if state.lights.len() >= MAX_LIGHTS {
+        return Err(SceneError::TooManyLights);
+    }
+    let id = LightId(state.next_light_id);
+    state.next_light_id += 1;
+    state.lights.push(Light {
+        id,
+        pos: desc.pos,
+        color: desc.color,
+        fov: desc.fov,
+        depth: desc.depth,
+        target_view: light_target_view(&state.shadow_texture, state.lights.len()),
+    });
+    state.lights_are_dirty = true;
+    Ok(id)
+}
+
+pub(crate) fn remove_light(state: &mut State, id: LightId) -> Result<(), SceneError> {
+    let index = state
+        .lights
+        .iter()
+        .position(|light| light.id == id)
+        .ok_or(SceneError::UnknownLight(id))?;
+    // 后面的光源前移一位,阴影层也要跟着前移
+    let mut removed = state.lights.remove(index);
+    for light in &mut state.lights[index..] {
+        std::mem::swap(&mut light.target_view, &mut removed.target_view);
+    }
+    state.lights_are_dirty = true;
+    Ok(())
+}
+
+pub(crate) fn update_light(state: &mut State, id: LightId, f: impl FnOnce(&mut LightDesc)) -> Result<(), SceneError> {
+    let light = state
+        .lights
+        .iter_mut()
+        .find(|light| light.id == id)
+        .ok_or(SceneError::UnknownLight(id))?;
+    let mut desc = light.desc();
+    f(&mut desc);
+    light.pos = desc.pos;
+    light.color = desc.color;
+    light.fov = desc.fov;
+    light.depth = desc.depth;
+    state.lights_are_dirty = true;
+    Ok(())
+}
+
+/// Replaces the default scene of a freshly created state with the entities and lights of `old`,
+/// which may live on a lost device. Ids stay the same.
+pub(crate) fn restore(state: &mut State, device: &wgpu::Device, old: &State) {
+    state.entities.clear();
+    state.lights.clear();
+    for entity in &old.entities {
+        spawn_entity(state, device, entity.desc());
+        if let Some(restored) = state.entities.last_mut() {
+            restored.id = entity.id;
+        }
+    }
+    for light in &old.lights {
+        // 原来的光源数量不会超过 MAX_LIGHTS
+        if add_light(state, light.desc()).is_ok() {
+            if let Some(restored) = state.lights.last_mut() {
+                restored.id = light.id;
+            }
+        }
+    }
+    state.next_entity_id = old.next_entity_id;
+    state.next_light_id = old.next_light_id;
+}
+
+/// One fixed simulation step: spins every entity around its local Y axis by `rotation_speed * dt`.
+pub(crate) fn update(state: &mut State, dt: f32) {
+    for entity in &mut state.entities {
+        // The last drawn frame may have been blended, settle it on `mx_world`
+        if entity.prev_world != entity.mx_world {
+            entity.prev_world = entity.mx_world;
+            entity.uniform_dirty = true;
+        }
+        if entity.rotation_speed != 0.0 {
+            entity.mx_world = entity.mx_world * Matrix4::from_angle_y(Rad(entity.rotation_speed * dt));
+            entity.uniform_dirty = true;
+        }
+    }
+}
+
+/// Keeps `current` when it still matches the renderer's depth test, otherwise makes a new one.
+fn forward_depth(renderer: &Renderer, current: Option<DepthBuffer>) -> Option<DepthBuffer> {
+    let depth_test = renderer.depth_test()?;
+    match current {
+        Some(depth)
+            if depth.format() == depth_test.format
+                && depth.sample_count() == renderer.sample_count()
+                && depth.size() == renderer.size() =>
+        {
+            Some(depth)
+        }
+        _ => Some(DepthBuffer::new(
+            renderer.device(),
+            depth_test.format,
+            renderer.sample_count(),
+            renderer.size(),
+        )),
+    }
+}
+
+pub(crate) fn resize(state: &mut State, device: &wgpu::Device, size: (u32, u32)) {
+    if let Some(depth) = &mut state.forward_depth {
+        depth.resize(device, size);
+    }
+}
+
+pub(crate) fn render(
+    state: &mut State,
+    camera: &Camera,
+    alpha: f32,
+    queue: &wgpu::Queue,
+    encoder: &mut wgpu::CommandEncoder,
+    target: ColorTarget,
+) {
+    // The camera may move every frame, so Globals is always rewritten
+    let globals = GlobalsRaw {
+        view_proj: camera.view_projection().into(),
+        num_lights: [state.lights.len() as u32, 0, 0, 0],
+    };
+    queue.write_buffer(&state.forward_pass.uniform_buf, 0, cast_slice(&[globals]));
+    if state.lights_are_dirty {
+        state.lights_are_dirty = false;
+        let raw: Vec<LightRaw> = state.lights.iter().map(Light::to_raw).collect();
+        queue.write_buffer(&state.light_uniform_buf, 0, cast_slice(&raw));
+    }
+    // Entities moved by the last update step are blended every frame until the next one
+    for entity in state.entities.iter_mut().filter(|entity| entity.uniform_dirty || entity.prev_world != entity.mx_world) {
+        entity.uniform_dirty = false;
+        queue.write_buffer(&entity.uniform_buf, 0, cast_slice(&[entity.to_raw(alpha)]));
+    }
+
+    // 1. 从每个光源的视角渲染深度,写入阴影纹理的对应层
+    for (i, light) in state.lights.iter().enumerate() {
+        encoder.copy_buffer_to_buffer(
+            &state.light_uniform_buf,
+            (i * size_of::<LightRaw>()) as wgpu::BufferAddress,
+            &state.shadow_pass.uniform_buf,
+            0,
+            size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+        );
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &light.target_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            ..Default::default()
+        });
+        pass.set_pipeline(&state.shadow_pass.pipeline);
+        pass.set_bind_group(0, &state.shadow_pass.bind_group, &[]);
+        draw_entities(&mut pass, &state.entities);
+    }
+
+    // 2. 正常渲染场景,采样阴影纹理
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Forward Pass"),
+        color_attachments: &[Some(target.attachment(wgpu::LoadOp::Clear(CLEAR_COLOR)))],
+        depth_stencil_attachment: state.forward_depth.as_ref().map(DepthBuffer::attachment),
+        ..Default::default()
+    });
+    pass.set_pipeline(&state.forward_pass.pipeline);
+    pass.set_bind_group(0, &state.forward_pass.bind_group, &[]);
+    draw_entities(&mut pass, &state.entities);
+}
+
+fn draw_entities<'a>(pass: &mut wgpu::RenderPass<'a>, entities: &'a [Entity]) {
+    for entity in entities {
+        pass.set_bind_group(1, &entity.bind_group, &[]);
+        pass.set_vertex_buffer(0, entity.vertex_buf.slice(..));
+        pass.set_index_buffer(entity.index_buf.slice(..), wgpu::IndexFormat::Uint16);
+        pass.draw_indexed(0..entity.index_count as u32, 0, 0..1);
+    }
+}
@@ -0,0 +1,241 @@
+use std::ops::Range;
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{ColorChoice, NoColor, StandardStream, WriteColor};
+use naga::{SourceLocation, Span, WithSpan};
+
+use super::preprocess::SourceMap;
+use super::IncludedFile;
+
+/// A single message produced by naga, with the span it points at resolved against the source.
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+    pub message: String,
+    pub labels: Vec<ShaderLabel>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShaderLabel {
+    /// `None` for the root source, otherwise the name of the `#include`d file `span` points into.
+    pub file: Option<String>,
+    pub span: Range<usize>,
+    pub location: SourceLocation,
+    pub message: String,
+    /// The full source line the label starts on.
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum ShaderTranslateError {
+    #[error("failed to parse {language} shader ({} error(s))", diagnostics.len())]
+    Parse {
+        language: &'static str,
+        code: String,
+        includes: Vec<IncludedFile>,
+        diagnostics: Vec<ShaderDiagnostic>,
+    },
+    #[error("failed to validate shader: {}", diagnostic.message)]
+    Validate {
+        code: String,
+        includes: Vec<IncludedFile>,
+        diagnostic: ShaderDiagnostic,
+    },
+    #[error("failed to emit {target}: {message}")]
+    Emit {
+        target: &'static str,
+        message: String,
+    },
+}
+
+impl ShaderLabel {
+    fn new(span: Span, message: impl Into<String>, source: &str) -> Option<Self> {
+        Some(Self::from_range(span.to_range()?, None, message, source))
+    }
+
+    pub(crate) fn from_range(range: Range<usize>, file: Option<String>, message: impl Into<String>, source: &str) -> Self {
+        let location = Span::new(range.start as u32, range.end as u32).location(source);
+        let line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[range.start..].find('\n').map_or(source.len(), |i| range.start + i);
+        Self {
+            file,
+            span: range,
+            location,
+            message: message.into(),
+            snippet: source[line_start..line_end].trim_end_matches('\r').to_string(),
+        }
+    }
+}
+
+impl ShaderDiagnostic {
+    /// `includes` gives the file id of each label: 0 is the root, `i + 1` is `includes[i]`.
+    fn to_codespan(&self, includes: &[IncludedFile]) -> Diagnostic<usize> {
+        let file_id = |label: &ShaderLabel| match &label.file {
+            Some(name) => includes.iter().position(|f| &f.name == name).map_or(0, |i| i + 1),
+            None => 0,
+        };
+        Diagnostic::error()
+            .with_message(&self.message)
+            .with_labels(
+                self.labels
+                    .iter()
+                    .map(|label| Label::primary(file_id(label), label.span.clone()).with_message(&label.message))
+                    .collect(),
+            )
+            .with_notes(self.notes.clone())
+    }
+}
+
+impl ShaderTranslateError {
+    pub fn diagnostics(&self) -> &[ShaderDiagnostic] {
+        match self {
+            Self::Parse { diagnostics, .. } => diagnostics,
+            Self::Validate { diagnostic, .. } => std::slice::from_ref(diagnostic),
+            Self::Emit { .. } => &[],
+        }
+    }
+
+    /// The first source location any diagnostic points at.
+    pub fn location(&self) -> Option<SourceLocation> {
+        self.diagnostics()
+            .iter()
+            .flat_map(|d| d.labels.first())
+            .map(|l| l.location)
+            .next()
+    }
+
+    pub fn emit_to_writer(&self, writer: &mut impl WriteColor, path: &str) {
+        let (source, includes) = match self {
+            Self::Parse { code, includes, .. } | Self::Validate { code, includes, .. } => (code.as_str(), includes.as_slice()),
+            Self::Emit { .. } => ("", &[][..]),
+        };
+        let mut files = SimpleFiles::new();
+        files.add(path.to_string(), source.to_string());
+        for include in includes {
+            files.add(include.name.clone(), include.code.clone());
+        }
+        let config = term::Config::default();
+        if self.diagnostics().is_empty() {
+            let diagnostic = Diagnostic::error().with_message(self.to_string());
+            term::emit(writer, &config, &files, &diagnostic).expect("cannot write error");
+        }
+        for diagnostic in self.diagnostics() {
+            term::emit(writer, &config, &files, &diagnostic.to_codespan(includes)).expect("cannot write error");
+        }
+    }
+
+    pub fn emit_to_stderr(&self, path: &str) {
+        let writer = StandardStream::stderr(ColorChoice::Auto);
+        self.emit_to_writer(&mut writer.lock(), path);
+    }
+
+    pub fn emit_to_string(&self, path: &str) -> String {
+        let mut writer = NoColor::new(Vec::new());
+        self.emit_to_writer(&mut writer, path);
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    /// Points labels from include-expanded source back at the root or included file they came from.
+    pub(crate) fn with_source_map(mut self, root: &str, map: &SourceMap) -> Self {
+        if map.files.is_empty() {
+            return self;
+        }
+        let (code, includes, diagnostics) = match &mut self {
+            Self::Parse { code, includes, diagnostics, .. } => (code, includes, diagnostics.as_mut_slice()),
+            Self::Validate { code, includes, diagnostic } => (code, includes, std::slice::from_mut(diagnostic)),
+            Self::Emit { .. } => return self,
+        };
+        for diagnostic in diagnostics {
+            for label in &mut diagnostic.labels {
+                *label = map.remap_label(label, root);
+            }
+        }
+        *code = root.to_string();
+        *includes = map.files.clone();
+        self
+    }
+
+    pub(crate) fn from_glsl(err: naga::front::glsl::ParseError, source: &str) -> Self {
+        let diagnostics = err
+            .errors
+            .iter()
+            .map(|e| ShaderDiagnostic {
+                message: e.kind.to_string(),
+                labels: ShaderLabel::new(e.meta, "", source).into_iter().collect(),
+                notes: Vec::new(),
+            })
+            .collect();
+        Self::Parse {
+            language: "GLSL",
+            code: source.to_string(),
+            includes: Vec::new(),
+            diagnostics,
+        }
+    }
+
+    pub(crate) fn from_wgsl(err: naga::front::wgsl::ParseError, source: &str) -> Self {
+        let diagnostic = ShaderDiagnostic {
+            message: err.message().to_string(),
+            labels: err
+                .labels()
+                .filter_map(|(span, msg)| ShaderLabel::new(span, msg, source))
+                .collect(),
+            notes: Vec::new(),
+        };
+        Self::Parse {
+            language: "WGSL",
+            code: source.to_string(),
+            includes: Vec::new(),
+            diagnostics: vec![diagnostic],
+        }
+    }
+
+    pub(crate) fn from_validation<E: std::error::Error>(err: WithSpan<E>, source: &str) -> Self {
+        let mut notes = Vec::new();
+        let mut cause: &dyn std::error::Error = err.as_inner();
+        while let Some(next) = cause.source() {
+            notes.push(next.to_string());
+            cause = next;
+        }
+        let diagnostic = ShaderDiagnostic {
+            message: err.as_inner().to_string(),
+            labels: err
+                .spans()
+                .filter_map(|(span, desc)| ShaderLabel::new(*span, desc.as_str(), source))
+                .collect(),
+            notes,
+        };
+        Self::Validate {
+            code: source.to_string(),
+            includes: Vec::new(),
+            diagnostic,
+        }
+    }
+
+    pub(crate) fn from_spirv(err: naga::front::spv::Error) -> Self {
+        Self::parse_message("SPIR-V", err)
+    }
+
+    /// A parse failure with no source span, e.g. non UTF-8 input.
+    pub(crate) fn parse_message(language: &'static str, err: impl std::fmt::Display) -> Self {
+        Self::Parse {
+            language,
+            code: String::new(),
+            includes: Vec::new(),
+            diagnostics: vec![ShaderDiagnostic {
+                message: err.to_string(),
+                labels: Vec::new(),
+                notes: Vec::new(),
+            }],
+        }
+    }
+
+    pub(crate) fn emit(target: &'static str, err: impl std::fmt::Display) -> Self {
+        Self::Emit {
+            target,
+            message: err.to_string(),
+        }
+    }
+}
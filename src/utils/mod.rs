@@ -0,0 +1,44 @@
+mod error;
+mod preprocess;
+mod translate;
+
+pub use error::*;
+pub use preprocess::*;
+pub use translate::*;
+
+pub(crate) use preprocess::expand_includes;
+
+use naga::back::msl::{self, TranslationInfo};
+use naga::ShaderStage;
+
+pub fn try_glsl_to_wgsl(glsl: &str, stage: ShaderStage) -> Result<String, ShaderTranslateError> {
+    let output = translate(glsl, SourceLang::Glsl(stage), TargetLang::Wgsl, &Default::default())?;
+    Ok(output.into_text().unwrap_or_default())
+}
+
+pub fn glsl_to_wgsl(glsl: &str, stage: ShaderStage) -> String {
+    try_glsl_to_wgsl(glsl, stage).unwrap_or_else(|e| panic!("{}", e.emit_to_string("glsl")))
+}
+
+// wgsl to msl
+pub fn try_wgsl_to_msl(wgsl: &str) -> Result<(String, TranslationInfo), ShaderTranslateError> {
+    let options = TranslateOptions::default();
+    let (module, info) = parse_module(wgsl, SourceLang::Wgsl, &options)?;
+    let msl_options = msl::Options {
+        lang_version: options.msl.lang_version,
+        ..Default::default()
+    };
+    msl::write_string(&module, &info, &msl_options, &Default::default())
+        .map_err(|e| ShaderTranslateError::emit("MSL", e))
+}
+
+pub fn wgsl_to_msl(wgsl: &str) -> (String, TranslationInfo) {
+    try_wgsl_to_msl(wgsl).unwrap_or_else(|e| panic!("{}", e.emit_to_string("wgsl")))
+}
+
+pub fn cast_slice<T>(data: &[T]) -> &[u8] {
+    use std::mem::size_of_val;
+    use std::slice::from_raw_parts;
+
+    unsafe { from_raw_parts(data.as_ptr() as *const u8, size_of_val(data)) }
+}
@@ -0,0 +1,553 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{ShaderDiagnostic, ShaderLabel, ShaderTranslateError};
+
+/// The text of a file pulled in by `#include`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludedFile {
+    /// Name used in diagnostics and to resolve nested includes relative to this file.
+    pub name: String,
+    pub code: String,
+}
+
+/// Looks up the file named by `#include "..."` or `#include <...>`.
+pub trait IncludeResolver: Send + Sync {
+    /// `includer` is the name of the file holding the directive, empty for the root source.
+    fn resolve(&self, requested: &str, includer: &str) -> Result<IncludedFile, String>;
+}
+
+/// Resolves includes relative to the including file first, then to `root`.
+#[derive(Debug, Clone)]
+pub struct FileIncludeResolver {
+    pub root: PathBuf,
+}
+
+impl FileIncludeResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl IncludeResolver for FileIncludeResolver {
+    fn resolve(&self, requested: &str, includer: &str) -> Result<IncludedFile, String> {
+        let relative = Path::new(includer).parent().map(|dir| dir.join(requested));
+        let candidates = relative.into_iter().chain(Some(self.root.join(requested)));
+        for path in candidates {
+            if let Ok(code) = std::fs::read_to_string(&path) {
+                return Ok(IncludedFile {
+                    name: path.to_string_lossy().into_owned(),
+                    code,
+                });
+            }
+        }
+        Err(format!("cannot find '{requested}' under {}", self.root.display()))
+    }
+}
+
+/// Resolves includes from a name → source map, for embedded or generated shaders.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIncludeResolver {
+    pub files: HashMap<String, String>,
+}
+
+impl MemoryIncludeResolver {
+    pub fn with_file(mut self, name: impl Into<String>, code: impl Into<String>) -> Self {
+        self.files.insert(name.into(), code.into());
+        self
+    }
+}
+
+impl IncludeResolver for MemoryIncludeResolver {
+    fn resolve(&self, requested: &str, _includer: &str) -> Result<IncludedFile, String> {
+        self.files
+            .get(requested)
+            .map(|code| IncludedFile {
+                name: requested.to_string(),
+                code: code.clone(),
+            })
+            .ok_or_else(|| format!("no in-memory file named '{requested}'"))
+    }
+}
+
+/// Options for the GLSL preprocessor that runs before naga parses the source.
+#[derive(Clone, Default)]
+pub struct PreprocessOptions {
+    /// `#define key value` pairs.
+    pub defines: BTreeMap<String, String>,
+    /// Without a resolver any `#include` is an error. Includes in blocks the defines switch
+    /// off are skipped; a condition that cannot be decided from the defines alone counts as on.
+    pub include_resolver: Option<Arc<dyn IncludeResolver>>,
+}
+
+impl fmt::Debug for PreprocessOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreprocessOptions")
+            .field("defines", &self.defines)
+            .field("include_resolver", &self.include_resolver.is_some())
+            .finish()
+    }
+}
+
+impl PreprocessOptions {
+    pub fn define(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.defines.insert(name.into(), value.to_string());
+        self
+    }
+
+    pub fn include_resolver(mut self, resolver: impl IncludeResolver + 'static) -> Self {
+        self.include_resolver = Some(Arc::new(resolver));
+        self
+    }
+}
+
+/// A run of the expanded source copied verbatim from one file.
+#[derive(Debug, Clone)]
+struct Segment {
+    expanded_start: usize,
+    /// Index into `SourceMap::files`, `None` for the root source.
+    file: Option<usize>,
+    original_start: usize,
+    len: usize,
+}
+
+/// Maps offsets in the include-expanded source back to the file they came from.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SourceMap {
+    pub files: Vec<IncludedFile>,
+    segments: Vec<Segment>,
+}
+
+impl SourceMap {
+    /// Returns the file index and the offset inside that file.
+    fn lookup(&self, offset: usize) -> (Option<usize>, usize) {
+        let index = self.segments.partition_point(|s| s.expanded_start <= offset);
+        match index.checked_sub(1).map(|i| &self.segments[i]) {
+            Some(s) => (s.file, s.original_start + (offset - s.expanded_start).min(s.len)),
+            None => (None, offset),
+        }
+    }
+
+    /// Re-targets a label that points into the expanded source.
+    pub(crate) fn remap_label(&self, label: &ShaderLabel, root: &str) -> ShaderLabel {
+        let (file, start) = self.lookup(label.span.start);
+        let len = label.span.len();
+        let (name, code) = match file {
+            Some(i) => (Some(self.files[i].name.clone()), self.files[i].code.as_str()),
+            None => (None, root),
+        };
+        let end = (start + len).min(code.len());
+        ShaderLabel::from_range(start..end, name, label.message.clone(), code)
+    }
+}
+
+/// Splices every `#include` into one source and records where each piece came from.
+pub(crate) fn expand_includes(root: &str, options: &PreprocessOptions) -> Result<(String, SourceMap), ShaderTranslateError> {
+    let mut expanded = String::with_capacity(root.len());
+    let mut map = SourceMap::default();
+    let mut stack = Vec::new();
+    let mut conditionals = Conditionals::new(&options.defines);
+    expand_file(root, None, options, &mut expanded, &mut map, &mut stack, &mut conditionals)
+        .map_err(|(file, span, message)| include_error(root, &map, file, span, message))?;
+    Ok((expanded, map))
+}
+
+type IncludeFailure = (Option<usize>, Range<usize>, String);
+
+fn expand_file(
+    code: &str,
+    file: Option<usize>,
+    options: &PreprocessOptions,
+    out: &mut String,
+    map: &mut SourceMap,
+    stack: &mut Vec<String>,
+    conditionals: &mut Conditionals,
+) -> Result<(), IncludeFailure> {
+    let mut copied_from = 0;
+    let mut offset = 0;
+    for line in code.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let Some((name, rest)) = parse_directive(line) else { continue };
+        if name != "include" {
+            conditionals.directive(name, rest);
+            continue;
+        }
+
+        push_segment(out, map, file, code, copied_from..line_start);
+        copied_from = offset;
+        if conditionals.skipping() {
+            // naga 的预处理器不认识 #include,即使在关掉的块里也会报错,只留一个空行
+            if line.ends_with('\n') {
+                out.push('\n');
+            }
+            continue;
+        }
+        let directive = line_start..line_start + line.trim_end().len();
+
+        let requested = parse_include(rest)
+            .ok_or((file, directive.clone(), "expected #include \"name\" or #include <name>".to_string()))?;
+        let resolver = options
+            .include_resolver
+            .as_ref()
+            .ok_or((file, directive.clone(), "#include used without an include resolver".to_string()))?;
+        let includer = file.map_or("", |i| map.files[i].name.as_str());
+        let included = resolver
+            .resolve(requested, includer)
+            .map_err(|e| (file, directive.clone(), e))?;
+        if stack.contains(&included.name) {
+            return Err((file, directive, format!("include cycle through '{}'", included.name)));
+        }
+
+        map.files.push(included.clone());
+        stack.push(included.name.clone());
+        expand_file(&included.code, Some(map.files.len() - 1), options, out, map, stack, conditionals)?;
+        stack.pop();
+        if !out.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+    push_segment(out, map, file, code, copied_from..code.len());
+    Ok(())
+}
+
+fn push_segment(out: &mut String, map: &mut SourceMap, file: Option<usize>, code: &str, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+    map.segments.push(Segment {
+        expanded_start: out.len(),
+        file,
+        original_start: range.start,
+        len: range.len(),
+    });
+    out.push_str(&code[range]);
+}
+
+/// `# name rest` → (`name`, `rest`), with a trailing `//` comment dropped from `rest`.
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = line.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(line.len());
+    let rest = &line[end..];
+    let rest = rest.find("//").map_or(rest, |i| &rest[..i]);
+    Some((&line[..end], rest.trim()))
+}
+
+/// `"name"` / `<name>` → `name`.
+fn parse_include(rest: &str) -> Option<&str> {
+    let (open, close) = match rest.chars().next()? {
+        '"' => ('"', '"'),
+        '<' => ('<', '>'),
+        _ => return None,
+    };
+    rest.strip_prefix(open)?.strip_suffix(close)
+}
+
+/// Whether a conditional block is on. Only what the defines decide is `Yes`/`No`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Truth {
+    Yes,
+    No,
+    Unknown,
+}
+
+impl Truth {
+    fn from_bool(value: bool) -> Self {
+        if value {
+            Self::Yes
+        } else {
+            Self::No
+        }
+    }
+
+    fn not(self) -> Self {
+        match self {
+            Self::Yes => Self::No,
+            Self::No => Self::Yes,
+            Self::Unknown => Self::Unknown,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Yes, _) | (_, Self::Yes) => Self::Yes,
+            (Self::No, Self::No) => Self::No,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// One `#if`..`#endif` block: whether the current branch is on and whether any branch so far was.
+#[derive(Debug, Clone, Copy)]
+struct Branch {
+    current: Truth,
+    taken: Truth,
+}
+
+/// Follows `#if` nesting and `#define`s across the root and included files, so includes in
+/// disabled blocks are skipped. Anything beyond `defined`, `!`, numbers and plain macros is
+/// left to naga's preprocessor and counts as on here.
+#[derive(Debug)]
+struct Conditionals {
+    defines: BTreeMap<String, String>,
+    /// Macros defined or undefined in a block whose condition was unknown.
+    uncertain: HashSet<String>,
+    stack: Vec<Branch>,
+}
+
+impl Conditionals {
+    fn new(defines: &BTreeMap<String, String>) -> Self {
+        Self {
+            defines: defines.clone(),
+            uncertain: HashSet::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// True inside a block that is certainly off.
+    fn skipping(&self) -> bool {
+        self.stack.iter().any(|b| b.current == Truth::No)
+    }
+
+    fn directive(&mut self, name: &str, rest: &str) {
+        match name {
+            "ifdef" => self.push(self.defined(rest)),
+            "ifndef" => self.push(self.defined(rest).not()),
+            "if" => self.push(self.eval(rest)),
+            "elif" => {
+                let condition = self.eval(rest);
+                if let Some(top) = self.stack.last_mut() {
+                    top.current = match top.taken {
+                        Truth::Yes => Truth::No,
+                        Truth::No => condition,
+                        Truth::Unknown if condition == Truth::No => Truth::No,
+                        Truth::Unknown => Truth::Unknown,
+                    };
+                    top.taken = top.taken.or(top.current);
+                }
+            }
+            "else" => {
+                if let Some(top) = self.stack.last_mut() {
+                    top.current = top.taken.not();
+                    top.taken = Truth::Yes;
+                }
+            }
+            "endif" => {
+                self.stack.pop();
+            }
+            "define" | "undef" if !self.skipping() => {
+                let (macro_name, value) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                if self.stack.iter().any(|b| b.current == Truth::Unknown) {
+                    self.uncertain.insert(macro_name.to_string());
+                } else if name == "define" {
+                    self.defines.insert(macro_name.to_string(), value.trim().to_string());
+                } else {
+                    self.defines.remove(macro_name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, condition: Truth) {
+        self.stack.push(Branch {
+            current: condition,
+            taken: condition,
+        });
+    }
+
+    fn defined(&self, name: &str) -> Truth {
+        let name = name.trim();
+        // GL_ 和 __ 开头的宏由 naga 的预处理器预定义
+        if self.uncertain.contains(name) || name.starts_with("GL_") || name.starts_with("__") || !is_identifier(name) {
+            return Truth::Unknown;
+        }
+        Truth::from_bool(self.defines.contains_key(name))
+    }
+
+    fn eval(&self, expr: &str) -> Truth {
+        let expr = expr.trim();
+        if let Some(rest) = expr.strip_prefix('!') {
+            return self.eval(rest).not();
+        }
+        if let Some(rest) = expr.strip_prefix("defined").filter(|r| r.starts_with([' ', '\t', '('])) {
+            let rest = rest.trim();
+            let name = rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')).unwrap_or(rest);
+            return self.defined(name);
+        }
+        if let Ok(value) = expr.parse::<i64>() {
+            return Truth::from_bool(value != 0);
+        }
+        match self.defined(expr) {
+            // 未定义的宏在 #if 中按 0 处理
+            Truth::No => Truth::No,
+            Truth::Yes => {
+                let value = self.defines[expr].parse::<i64>();
+                value.map_or(Truth::Unknown, |value| Truth::from_bool(value != 0))
+            }
+            Truth::Unknown => Truth::Unknown,
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn include_error(root: &str, map: &SourceMap, file: Option<usize>, span: Range<usize>, message: String) -> ShaderTranslateError {
+    let (name, code) = match file {
+        Some(i) => (Some(map.files[i].name.clone()), map.files[i].code.as_str()),
+        None => (None, root),
+    };
+    ShaderTranslateError::Parse {
+        language: "GLSL",
+        code: root.to_string(),
+        includes: map.files.clone(),
+        diagnostics: vec![ShaderDiagnostic {
+            message,
+            labels: vec![ShaderLabel::from_range(span, name, String::new(), code)],
+            notes: Vec::new(),
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{parse_module, SourceLang, TranslateOptions};
+
+    fn options(files: &[(&str, &str)]) -> PreprocessOptions {
+        let resolver = files
+            .iter()
+            .fold(MemoryIncludeResolver::default(), |r, (name, code)| r.with_file(*name, *code));
+        PreprocessOptions::default().include_resolver(resolver)
+    }
+
+    fn message(error: ShaderTranslateError) -> String {
+        match error {
+            ShaderTranslateError::Parse { diagnostics, .. } => diagnostics[0].message.clone(),
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn splices_nested_includes() {
+        let options = options(&[("a.glsl", "float a;\n#include \"b.glsl\"\n"), ("b.glsl", "float b;")]);
+        let (expanded, map) = expand_includes("#include \"a.glsl\"\nvoid main() {}\n", &options).unwrap();
+        assert_eq!(expanded, "float a;\nfloat b;\nvoid main() {}\n");
+        let names: Vec<_> = map.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a.glsl", "b.glsl"]);
+    }
+
+    #[test]
+    fn include_without_resolver_fails() {
+        let error = expand_includes("#include <a.glsl>\n", &PreprocessOptions::default()).unwrap_err();
+        assert!(message(error).contains("without an include resolver"));
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let options = options(&[("a.glsl", "#include \"b.glsl\"\n"), ("b.glsl", "#include \"a.glsl\"\n")]);
+        let error = expand_includes("#include \"a.glsl\"\n", &options).unwrap_err();
+        assert!(message(error).contains("include cycle through 'a.glsl'"));
+    }
+
+    #[test]
+    fn skips_includes_in_disabled_blocks() {
+        let options = options(&[("on.glsl", "float on;\n")]).define("ON", 1);
+        let source = "\
+#ifdef NOPE
+#include \"missing.glsl\"
+#elif defined(ON)
+#include \"on.glsl\"
+#else
+#include \"missing.glsl\"
+#endif
+#if 0 // 注释
+#include \"missing.glsl\"
+#endif
+#ifndef ON
+#include \"missing.glsl\"
+#endif
+";
+        let (expanded, map) = expand_includes(source, &options).unwrap();
+        assert!(expanded.contains("float on;"));
+        assert_eq!(map.files.len(), 1);
+    }
+
+    #[test]
+    fn follows_defines_made_in_the_source() {
+        let options = options(&[("b.glsl", "#define FROM_B\n")]);
+        let source = "\
+#define LOCAL 0
+#include \"b.glsl\"
+#if LOCAL
+#include \"missing.glsl\"
+#endif
+#ifndef FROM_B
+#include \"missing.glsl\"
+#endif
+";
+        assert!(expand_includes(source, &options).is_ok());
+    }
+
+    #[test]
+    fn resolves_includes_under_conditions_it_cannot_decide() {
+        let options = options(&[]).define("A", 1);
+        for condition in ["#if A > 0", "#ifdef GL_ES", "#if defined(A) && defined(B)"] {
+            let source = format!("{condition}\n#include \"missing.glsl\"\n#endif\n");
+            let error = expand_includes(&source, &options).unwrap_err();
+            assert!(message(error).contains("missing.glsl"), "{condition}");
+        }
+    }
+
+    #[test]
+    fn guarded_missing_include_still_parses() {
+        let mut options = TranslateOptions::default();
+        options.preprocess = self::options(&[]);
+        let source = "\
+#version 450
+#ifdef NOPE
+#include \"missing.glsl\"
+#endif
+void main() { gl_Position = vec4(0.0); }
+";
+        parse_module(source, SourceLang::Glsl(naga::ShaderStage::Vertex), &options).unwrap();
+    }
+
+    #[test]
+    fn remaps_labels_into_included_files() {
+        let options = options(&[("inc.glsl", "float x;\nfloat y;\n")]);
+        let root = "// root\n#include \"inc.glsl\"\nvoid main() {}\n";
+        let (expanded, map) = expand_includes(root, &options).unwrap();
+
+        let y = expanded.find("float y").unwrap();
+        let label = ShaderLabel::from_range(y..y + 7, None, "here", &expanded);
+        let remapped = map.remap_label(&label, root);
+        assert_eq!(remapped.file.as_deref(), Some("inc.glsl"));
+        assert_eq!(remapped.span, 9..16);
+        assert_eq!(remapped.location.line_number, 2);
+        assert_eq!(remapped.snippet, "float y;");
+
+        let main = expanded.find("void main").unwrap();
+        let label = ShaderLabel::from_range(main..main + 4, None, "here", &expanded);
+        let remapped = map.remap_label(&label, root);
+        assert_eq!(remapped.file, None);
+        assert_eq!(remapped.location.line_number, 3);
+        assert_eq!(&root[remapped.span], "void");
+    }
+
+    #[test]
+    fn parse_errors_point_into_the_included_file() {
+        let mut options = TranslateOptions::default();
+        options.preprocess = self::options(&[("bad.glsl", "float x = ;\n")]);
+        let source = "#version 450\n#include \"bad.glsl\"\nvoid main() {}\n";
+        let error = parse_module(source, SourceLang::Glsl(naga::ShaderStage::Vertex), &options).unwrap_err();
+        let ShaderTranslateError::Parse { diagnostics, .. } = error else { panic!("expected a parse error") };
+        assert_eq!(diagnostics[0].labels[0].file.as_deref(), Some("bad.glsl"));
+    }
+}
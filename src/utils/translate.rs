@@ -0,0 +1,278 @@
+use naga::back::{glsl, hlsl, msl, spv, wgsl};
+use naga::valid::{Capabilities, ModuleInfo, ValidationFlags, Validator};
+use naga::{Module, ShaderStage};
+
+use super::preprocess::expand_includes;
+use super::{PreprocessOptions, ShaderTranslateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceLang {
+    /// GLSL has no entry point attributes, so the stage has to be given up front.
+    Glsl(ShaderStage),
+    Wgsl,
+    /// SPIR-V binary, little-endian words (naga does not accept big-endian modules).
+    SpirV,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetLang {
+    Wgsl,
+    Msl,
+    Hlsl,
+    /// Desktop or ES, chosen by [`GlslOptions::version`].
+    Glsl,
+    SpirV,
+}
+
+#[derive(Debug, Clone)]
+pub struct MslOptions {
+    /// (major, minor) Metal Shading Language version.
+    pub lang_version: (u8, u8),
+    pub fake_missing_bindings: bool,
+}
+
+impl Default for MslOptions {
+    fn default() -> Self {
+        Self {
+            lang_version: (2, 1),
+            fake_missing_bindings: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HlslOptions {
+    pub shader_model: hlsl::ShaderModel,
+    pub fake_missing_bindings: bool,
+}
+
+impl Default for HlslOptions {
+    fn default() -> Self {
+        Self {
+            shader_model: hlsl::ShaderModel::V5_1,
+            fake_missing_bindings: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlslOptions {
+    /// e.g. `Version::Desktop(450)` or `Version::new_gles(300)`.
+    pub version: glsl::Version,
+    /// GLSL output holds a single entry point. `None` picks the only one in the module.
+    pub entry_point: Option<String>,
+    pub writer_flags: glsl::WriterFlags,
+}
+
+impl Default for GlslOptions {
+    fn default() -> Self {
+        Self {
+            version: glsl::Version::Desktop(450),
+            entry_point: None,
+            writer_flags: glsl::WriterFlags::empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpirvOptions {
+    /// (major, minor) SPIR-V version.
+    pub lang_version: (u8, u8),
+    pub flags: spv::WriterFlags,
+}
+
+impl Default for SpirvOptions {
+    fn default() -> Self {
+        Self {
+            lang_version: (1, 0),
+            flags: spv::Options::default().flags,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TranslateOptions {
+    /// Capabilities the validator accepts. Empty matches what wgpu allows without extra features.
+    pub capabilities: Capabilities,
+    /// Defines and `#include` handling for GLSL input.
+    pub preprocess: PreprocessOptions,
+    pub msl: MslOptions,
+    pub hlsl: HlslOptions,
+    pub glsl: GlslOptions,
+    pub spirv: SpirvOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderOutput {
+    Text(String),
+    SpirV(Vec<u32>),
+}
+
+impl ShaderOutput {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::SpirV(_) => None,
+        }
+    }
+
+    pub fn into_text(self) -> Option<String> {
+        match self {
+            Self::Text(text) => Some(text),
+            Self::SpirV(_) => None,
+        }
+    }
+
+    /// Bytes ready to be written to a file; SPIR-V words are little endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Text(text) => text.as_bytes().to_vec(),
+            Self::SpirV(words) => words.iter().flat_map(|w| w.to_le_bytes()).collect(),
+        }
+    }
+}
+
+fn as_text<'a>(source: &'a [u8], language: &'static str) -> Result<&'a str, ShaderTranslateError> {
+    std::str::from_utf8(source).map_err(|e| ShaderTranslateError::parse_message(language, e))
+}
+
+pub(crate) fn validate(
+    module: &Module,
+    source: &str,
+    capabilities: Capabilities,
+) -> Result<ModuleInfo, ShaderTranslateError> {
+    let mut validator = Validator::new(ValidationFlags::all(), capabilities);
+    validator
+        .validate(module)
+        .map_err(|e| ShaderTranslateError::from_validation(e, source))
+}
+
+/// Parses and validates `source` into naga IR.
+pub fn parse_module(
+    source: impl AsRef<[u8]>,
+    from: SourceLang,
+    options: &TranslateOptions,
+) -> Result<(Module, ModuleInfo), ShaderTranslateError> {
+    let source = source.as_ref();
+    let (module, text) = match from {
+        SourceLang::Glsl(stage) => {
+            let text = as_text(source, "GLSL")?;
+            let (expanded, source_map) = expand_includes(text, &options.preprocess)?;
+            let mut glsl_options = naga::front::glsl::Options::from(stage);
+            glsl_options.defines.extend(options.preprocess.defines.clone());
+            let module = naga::front::glsl::Frontend::default()
+                .parse(&glsl_options, &expanded)
+                .map_err(|e| ShaderTranslateError::from_glsl(e, &expanded).with_source_map(text, &source_map))?;
+            let info = validate(&module, &expanded, options.capabilities)
+                .map_err(|e| e.with_source_map(text, &source_map))?;
+            return Ok((module, info));
+        }
+        SourceLang::Wgsl => {
+            let text = as_text(source, "WGSL")?;
+            let module = naga::front::wgsl::parse_str(text).map_err(|e| ShaderTranslateError::from_wgsl(e, text))?;
+            (module, text)
+        }
+        SourceLang::SpirV => {
+            let module = naga::front::spv::parse_u8_slice(source, &Default::default())
+                .map_err(ShaderTranslateError::from_spirv)?;
+            (module, "")
+        }
+    };
+    let info = validate(&module, text, options.capabilities)?;
+    Ok((module, info))
+}
+
+/// Writes an already validated module in the `to` language.
+pub fn write_module(
+    module: &Module,
+    info: &ModuleInfo,
+    to: TargetLang,
+    options: &TranslateOptions,
+) -> Result<ShaderOutput, ShaderTranslateError> {
+    match to {
+        TargetLang::Wgsl => wgsl::write_string(module, info, wgsl::WriterFlags::all())
+            .map(ShaderOutput::Text)
+            .map_err(|e| ShaderTranslateError::emit("WGSL", e)),
+        TargetLang::Msl => {
+            let msl_options = msl::Options {
+                lang_version: options.msl.lang_version,
+                fake_missing_bindings: options.msl.fake_missing_bindings,
+                ..Default::default()
+            };
+            msl::write_string(module, info, &msl_options, &Default::default())
+                .map(|(code, _)| ShaderOutput::Text(code))
+                .map_err(|e| ShaderTranslateError::emit("MSL", e))
+        }
+        TargetLang::Hlsl => {
+            let hlsl_options = hlsl::Options {
+                shader_model: options.hlsl.shader_model,
+                fake_missing_bindings: options.hlsl.fake_missing_bindings,
+                ..Default::default()
+            };
+            let mut code = String::new();
+            hlsl::Writer::new(&mut code, &hlsl_options)
+                .write(module, info)
+                .map_err(|e| ShaderTranslateError::emit("HLSL", e))?;
+            Ok(ShaderOutput::Text(code))
+        }
+        TargetLang::Glsl => {
+            let entry_point = match &options.glsl.entry_point {
+                Some(name) => module.entry_points.iter().find(|ep| &ep.name == name),
+                None if module.entry_points.len() == 1 => module.entry_points.first(),
+                None => None,
+            }
+            .ok_or_else(|| {
+                ShaderTranslateError::emit(
+                    "GLSL",
+                    format!(
+                        "pick one of {} entry point(s) with GlslOptions::entry_point",
+                        module.entry_points.len()
+                    ),
+                )
+            })?;
+            let glsl_options = glsl::Options {
+                version: options.glsl.version,
+                writer_flags: options.glsl.writer_flags,
+                ..Default::default()
+            };
+            let pipeline_options = glsl::PipelineOptions {
+                shader_stage: entry_point.stage,
+                entry_point: entry_point.name.clone(),
+                multiview: None,
+            };
+            let mut code = String::new();
+            glsl::Writer::new(
+                &mut code,
+                module,
+                info,
+                &glsl_options,
+                &pipeline_options,
+                Default::default(),
+            )
+            .and_then(|mut writer| writer.write())
+            .map_err(|e| ShaderTranslateError::emit("GLSL", e))?;
+            Ok(ShaderOutput::Text(code))
+        }
+        TargetLang::SpirV => {
+            let spv_options = spv::Options {
+                lang_version: options.spirv.lang_version,
+                flags: options.spirv.flags,
+                ..Default::default()
+            };
+            spv::write_vec(module, info, &spv_options, None)
+                .map(ShaderOutput::SpirV)
+                .map_err(|e| ShaderTranslateError::emit("SPIR-V", e))
+        }
+    }
+}
+
+/// Any-to-any shader translation: parse, validate and write in one go.
+pub fn translate(
+    source: impl AsRef<[u8]>,
+    from: SourceLang,
+    to: TargetLang,
+    options: &TranslateOptions,
+) -> Result<ShaderOutput, ShaderTranslateError> {
+    let (module, info) = parse_module(source, from, options)?;
+    write_module(&module, &info, to, options)
+}
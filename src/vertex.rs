@@ -1,5 +1,7 @@
+use crate::vertex_layout::VertexLayout;
+
 #[repr(C)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, VertexLayout)]
 pub struct Vertex {
     pub position: [i8; 4],
     pub normal: [i8; 4],
@@ -15,9 +17,9 @@ pub fn vertex(pos: [i8; 3], nor: [i8; 3]) -> Vertex {
 pub fn create_cube() -> (Vec<Vertex>, Vec<u16>) {
     let vertex_data = [
         vertex([-1, -1, -1], [0, 0, -1]),
-        vertex([1, -1, -1], [0, 0, -1]),
-        vertex([1, 1, -1], [0, 0, -1]),
         vertex([-1, 1, -1], [0, 0, -1]),
+        vertex([1, 1, -1], [0, 0, -1]),
+        vertex([1, -1, -1], [0, 0, -1]),
         vertex([-1, -1, 1], [0, 0, 1]),
         vertex([1, -1, 1], [0, 0, 1]),
         vertex([1, 1, 1], [0, 0, 1]),
@@ -27,13 +29,13 @@ pub fn create_cube() -> (Vec<Vertex>, Vec<u16>) {
         vertex([1, -1, 1], [0, -1, 0]),
         vertex([-1, -1, 1], [0, -1, 0]),
         vertex([-1, 1, -1], [0, 1, 0]),
-        vertex([1, 1, -1], [0, 1, 0]),
-        vertex([1, 1, 1], [0, 1, 0]),
         vertex([-1, 1, 1], [0, 1, 0]),
+        vertex([1, 1, 1], [0, 1, 0]),
+        vertex([1, 1, -1], [0, 1, 0]),
         vertex([-1, -1, -1], [-1, 0, 0]),
-        vertex([-1, 1, -1], [-1, 0, 0]),
-        vertex([-1, 1, 1], [-1, 0, 0]),
         vertex([-1, -1, 1], [-1, 0, 0]),
+        vertex([-1, 1, 1], [-1, 0, 0]),
+        vertex([-1, 1, -1], [-1, 0, 0]),
         vertex([1, -1, -1], [1, 0, 0]),
         vertex([1, 1, -1], [1, 0, 0]),
         vertex([1, 1, 1], [1, 0, 0]),
@@ -55,9 +57,9 @@ pub fn create_cube() -> (Vec<Vertex>, Vec<u16>) {
 pub fn create_plane() -> (Vec<Vertex>, Vec<u16>) {
     let vertex_data = [
         vertex([-1, 0, -1], [0, 1, 0]),
-        vertex([1, 0, -1], [0, 1, 0]),
-        vertex([1, 0, 1], [0, 1, 0]),
         vertex([-1, 0, 1], [0, 1, 0]),
+        vertex([1, 0, 1], [0, 1, 0]),
+        vertex([1, 0, -1], [0, 1, 0]),
     ];
 
     let index_data = [
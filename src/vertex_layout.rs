@@ -0,0 +1,387 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use naga::ScalarKind;
+
+use crate::reflection::{IoType, ShaderReflection};
+
+pub use glsl_naga_derive::VertexLayout;
+#[doc(hidden)]
+pub use wgpu;
+
+/// Describes how a Rust vertex struct is laid out in a vertex buffer.
+///
+/// Usually derived; see [`glsl_naga_derive::VertexLayout`] for the field attributes.
+pub trait VertexLayout: Sized {
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+    const STEP_MODE: wgpu::VertexStepMode = wgpu::VertexStepMode::Vertex;
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: Self::STEP_MODE,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// The vertex format a field type is uploaded as when no `#[vertex(format = ...)]` is given.
+pub trait VertexAttributeType {
+    const FORMAT: wgpu::VertexFormat;
+}
+
+macro_rules! vertex_attribute_types {
+    ($($ty:ty => $format:ident,)*) => {
+        $(impl VertexAttributeType for $ty {
+            const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+        })*
+    };
+}
+
+vertex_attribute_types! {
+    f32 => Float32, [f32; 1] => Float32, [f32; 2] => Float32x2, [f32; 3] => Float32x3, [f32; 4] => Float32x4,
+    f64 => Float64, [f64; 1] => Float64, [f64; 2] => Float64x2, [f64; 3] => Float64x3, [f64; 4] => Float64x4,
+    i32 => Sint32, [i32; 1] => Sint32, [i32; 2] => Sint32x2, [i32; 3] => Sint32x3, [i32; 4] => Sint32x4,
+    u32 => Uint32, [u32; 1] => Uint32, [u32; 2] => Uint32x2, [u32; 3] => Uint32x3, [u32; 4] => Uint32x4,
+    [i16; 2] => Sint16x2, [i16; 4] => Sint16x4,
+    [u16; 2] => Uint16x2, [u16; 4] => Uint16x4,
+    [i8; 2] => Sint8x2, [i8; 4] => Sint8x4,
+    [u8; 2] => Uint8x2, [u8; 4] => Uint8x4,
+    cgmath::Vector2<f32> => Float32x2, cgmath::Vector3<f32> => Float32x3, cgmath::Vector4<f32> => Float32x4,
+    cgmath::Point2<f32> => Float32x2, cgmath::Point3<f32> => Float32x3,
+}
+
+/// One shader input that the vertex buffers do not feed correctly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VertexMismatch {
+    /// No buffer provides this location.
+    Missing { location: u32, name: String, shader: IoType },
+    /// The buffer format cannot be read as the type the shader declares.
+    Format {
+        location: u32,
+        name: String,
+        shader: IoType,
+        format: wgpu::VertexFormat,
+    },
+    /// The attribute runs past the end of a vertex.
+    Stride {
+        location: u32,
+        format: wgpu::VertexFormat,
+        offset: wgpu::BufferAddress,
+        stride: wgpu::BufferAddress,
+    },
+    /// More than one attribute is bound to this location.
+    Duplicate { location: u32 },
+}
+
+impl VertexMismatch {
+    pub fn location(&self) -> u32 {
+        match *self {
+            Self::Missing { location, .. }
+            | Self::Format { location, .. }
+            | Self::Stride { location, .. }
+            | Self::Duplicate { location } => location,
+        }
+    }
+}
+
+impl fmt::Display for VertexMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing { location, name, shader } => {
+                write!(f, "location {location} ({name}: {shader}) has no vertex attribute")
+            }
+            Self::Format {
+                location,
+                name,
+                shader,
+                format,
+            } => write!(f, "location {location} ({name}: {shader}) is fed {format:?}"),
+            Self::Stride {
+                location,
+                format,
+                offset,
+                stride,
+            } => write!(
+                f,
+                "location {location} reads {format:?} at offset {offset}, past the {stride}-byte stride"
+            ),
+            Self::Duplicate { location } => write!(f, "location {location} is bound by more than one attribute"),
+        }
+    }
+}
+
+/// Every disagreement between a shader's vertex inputs and the buffers bound to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayoutError {
+    pub entry_point: String,
+    pub mismatches: Vec<VertexMismatch>,
+}
+
+impl fmt::Display for VertexLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "vertex buffers do not match the inputs of '{}':", self.entry_point)?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n  {mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for VertexLayoutError {}
+
+/// Checks the buffers a pipeline will bind against the reflected vertex inputs.
+///
+/// Attributes at locations the shader does not read are allowed, as they are in wgpu.
+pub fn check_vertex_buffers(
+    reflection: &ShaderReflection,
+    buffers: &[wgpu::VertexBufferLayout],
+) -> Result<(), VertexLayoutError> {
+    let mut mismatches = Vec::new();
+    let mut by_location = BTreeMap::new();
+    for buffer in buffers {
+        for attribute in buffer.attributes {
+            let location = attribute.shader_location;
+            if by_location.insert(location, attribute.format).is_some() {
+                mismatches.push(VertexMismatch::Duplicate { location });
+            }
+            let end = attribute.offset + attribute.format.size();
+            if buffer.array_stride != 0 && end > buffer.array_stride {
+                mismatches.push(VertexMismatch::Stride {
+                    location,
+                    format: attribute.format,
+                    offset: attribute.offset,
+                    stride: buffer.array_stride,
+                });
+            }
+        }
+    }
+
+    for input in &reflection.vertex_inputs {
+        let mismatch = match by_location.get(&input.location) {
+            None => VertexMismatch::Missing {
+                location: input.location,
+                name: input.name.clone(),
+                shader: input.ty,
+            },
+            Some(&format) if !format_matches(format, input.ty) => VertexMismatch::Format {
+                location: input.location,
+                name: input.name.clone(),
+                shader: input.ty,
+                format,
+            },
+            Some(_) => continue,
+        };
+        mismatches.push(mismatch);
+    }
+
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    mismatches.sort_by_key(VertexMismatch::location);
+    Err(VertexLayoutError {
+        entry_point: reflection.entry_point.clone(),
+        mismatches,
+    })
+}
+
+/// Normalized formats read as floats; integer formats must match the shader's signedness.
+fn format_matches(format: wgpu::VertexFormat, shader: IoType) -> bool {
+    use wgpu::VertexFormat::*;
+    let (kind, width, components) = match format {
+        Uint8x2 | Uint16x2 | Uint32x2 => (ScalarKind::Uint, 4, 2),
+        Uint8x4 | Uint16x4 | Uint32x4 => (ScalarKind::Uint, 4, 4),
+        Uint32 => (ScalarKind::Uint, 4, 1),
+        Uint32x3 => (ScalarKind::Uint, 4, 3),
+        Sint8x2 | Sint16x2 | Sint32x2 => (ScalarKind::Sint, 4, 2),
+        Sint8x4 | Sint16x4 | Sint32x4 => (ScalarKind::Sint, 4, 4),
+        Sint32 => (ScalarKind::Sint, 4, 1),
+        Sint32x3 => (ScalarKind::Sint, 4, 3),
+        Unorm8x2 | Snorm8x2 | Unorm16x2 | Snorm16x2 | Float16x2 | Float32x2 => (ScalarKind::Float, 4, 2),
+        Unorm8x4 | Snorm8x4 | Unorm16x4 | Snorm16x4 | Float16x4 | Float32x4 | Unorm10_10_10_2 => {
+            (ScalarKind::Float, 4, 4)
+        }
+        Float32 => (ScalarKind::Float, 4, 1),
+        Float32x3 => (ScalarKind::Float, 4, 3),
+        Float64 => (ScalarKind::Float, 8, 1),
+        Float64x2 => (ScalarKind::Float, 8, 2),
+        Float64x3 => (ScalarKind::Float, 8, 3),
+        Float64x4 => (ScalarKind::Float, 8, 4),
+    };
+    kind == shader.kind && width == shader.width && components == shader.components
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::ShaderStage;
+
+    use super::*;
+    use crate::reflection::ShaderIo;
+
+    fn io(location: u32, kind: ScalarKind, components: u8) -> ShaderIo {
+        ShaderIo {
+            location,
+            name: format!("in{location}"),
+            ty: IoType {
+                kind,
+                width: 4,
+                components,
+            },
+        }
+    }
+
+    fn reflection(vertex_inputs: Vec<ShaderIo>) -> ShaderReflection {
+        ShaderReflection {
+            stage: ShaderStage::Vertex,
+            entry_point: "main".to_string(),
+            vertex_inputs,
+            resources: Vec::new(),
+            push_constant_size: None,
+            fragment_outputs: Vec::new(),
+        }
+    }
+
+    fn attribute(location: u32, format: wgpu::VertexFormat, offset: u64) -> wgpu::VertexAttribute {
+        wgpu::VertexAttribute {
+            format,
+            offset,
+            shader_location: location,
+        }
+    }
+
+    fn buffer(stride: u64, attributes: &[wgpu::VertexAttribute]) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: stride,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes,
+        }
+    }
+
+    fn mismatches(reflection: &ShaderReflection, buffers: &[wgpu::VertexBufferLayout]) -> Vec<VertexMismatch> {
+        check_vertex_buffers(reflection, buffers).map_or_else(|e| e.mismatches, |()| Vec::new())
+    }
+
+    #[test]
+    fn matching_buffers_pass() {
+        use wgpu::VertexFormat::*;
+        let shader = reflection(vec![io(0, ScalarKind::Float, 3), io(1, ScalarKind::Float, 4)]);
+        let attributes = [attribute(0, Float32x3, 0), attribute(1, Unorm8x4, 12), attribute(5, Float32, 16)];
+        assert_eq!(check_vertex_buffers(&shader, &[buffer(20, &attributes)]), Ok(()));
+    }
+
+    #[test]
+    fn reports_missing_locations() {
+        let shader = reflection(vec![io(0, ScalarKind::Float, 2), io(2, ScalarKind::Float, 2)]);
+        let attributes = [attribute(0, wgpu::VertexFormat::Float32x2, 0)];
+        let found = mismatches(&shader, &[buffer(8, &attributes)]);
+        assert!(matches!(found[..], [VertexMismatch::Missing { location: 2, .. }]));
+    }
+
+    #[test]
+    fn reports_format_mismatches() {
+        use wgpu::VertexFormat::*;
+        let shader = reflection(vec![
+            io(0, ScalarKind::Float, 4),
+            io(1, ScalarKind::Sint, 2),
+            io(2, ScalarKind::Uint, 1),
+        ]);
+        let attributes = [attribute(0, Float32x3, 0), attribute(1, Uint32x2, 12), attribute(2, Uint32, 20)];
+        let found = mismatches(&shader, &[buffer(24, &attributes)]);
+        assert!(matches!(
+            found[..],
+            [
+                VertexMismatch::Format { location: 0, format: Float32x3, .. },
+                VertexMismatch::Format { location: 1, format: Uint32x2, .. },
+            ]
+        ));
+    }
+
+    #[test]
+    fn reports_attributes_past_the_stride() {
+        let shader = reflection(vec![io(0, ScalarKind::Float, 4)]);
+        let attributes = [attribute(0, wgpu::VertexFormat::Float32x4, 8)];
+        let found = mismatches(&shader, &[buffer(16, &attributes)]);
+        assert_eq!(
+            found,
+            [VertexMismatch::Stride {
+                location: 0,
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 8,
+                stride: 16,
+            }]
+        );
+        // stride 0 表示每个顶点共用同一份数据,不检查
+        assert_eq!(mismatches(&shader, &[buffer(0, &attributes)]), []);
+    }
+
+    #[test]
+    fn reports_duplicate_locations_across_buffers() {
+        use wgpu::VertexFormat::*;
+        let shader = reflection(vec![io(0, ScalarKind::Float, 2)]);
+        let (first, second) = ([attribute(0, Float32x2, 0)], [attribute(0, Float32x2, 0)]);
+        let found = mismatches(&shader, &[buffer(8, &first), buffer(8, &second)]);
+        assert_eq!(found, [VertexMismatch::Duplicate { location: 0 }]);
+    }
+
+    #[test]
+    fn normalized_and_integer_formats() {
+        use wgpu::VertexFormat::*;
+        let float4 = IoType {
+            kind: ScalarKind::Float,
+            width: 4,
+            components: 4,
+        };
+        let uint2 = IoType {
+            kind: ScalarKind::Uint,
+            components: 2,
+            ..float4
+        };
+        assert!(format_matches(Unorm8x4, float4));
+        assert!(format_matches(Float16x4, float4));
+        assert!(!format_matches(Uint8x4, float4));
+        assert!(format_matches(Uint16x2, uint2));
+        assert!(!format_matches(Sint16x2, uint2));
+        assert!(!format_matches(Float32x2, uint2));
+    }
+
+    #[derive(Clone, Copy, VertexLayout)]
+    #[repr(C)]
+    #[allow(dead_code)]
+    struct Derived {
+        position: [f32; 3],
+        #[vertex(format = Unorm8x4)]
+        color: [u8; 4],
+        #[vertex(skip)]
+        id: u32,
+        #[vertex(location = 4)]
+        uv: cgmath::Vector2<f32>,
+        weight: f32,
+    }
+
+    #[test]
+    fn derive_follows_field_attributes() {
+        use wgpu::VertexFormat::*;
+        assert_eq!(
+            Derived::ATTRIBUTES,
+            [
+                attribute(0, Float32x3, 0),
+                attribute(1, Unorm8x4, 12),
+                attribute(4, Float32x2, 20),
+                attribute(5, Float32, 28),
+            ]
+        );
+        let layout = Derived::layout();
+        assert_eq!(layout.array_stride, std::mem::size_of::<Derived>() as u64);
+        assert_eq!(layout.step_mode, wgpu::VertexStepMode::Vertex);
+    }
+
+    #[test]
+    fn derived_layout_checks_against_reflection() {
+        let shader = reflection(vec![
+            io(0, ScalarKind::Float, 3),
+            io(1, ScalarKind::Float, 4),
+            io(4, ScalarKind::Float, 2),
+        ]);
+        assert_eq!(check_vertex_buffers(&shader, &[Derived::layout()]), Ok(()));
+    }
+}
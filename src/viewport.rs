@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::config::{render_format, AppConfig};
+use crate::gui_tools::GuiRenderer;
+use crate::renderer::Renderer;
+
+/// One OS window and what it draws into: its own surface, surface configuration and egui
+/// state. The device and queue are the renderer's, shared by every window.
+#[derive(Debug)]
+pub(crate) struct Viewport {
+    pub window: Arc<Window>,
+    /// Only `None` while [`Self::recreate`] swaps it out.
+    surface: Option<wgpu::Surface<'static>>,
+    pub config: wgpu::SurfaceConfiguration,
+    pub gui: GuiRenderer,
+    /// False while minimized, nothing is drawn then.
+    pub visible: bool,
+    pub factor: f64,
+    pub modifiers: winit::keyboard::ModifiersState,
+}
+
+impl Viewport {
+    pub fn from_parts(
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+        gui: GuiRenderer,
+    ) -> Self {
+        let size = window.inner_size();
+        Self {
+            factor: window.scale_factor(),
+            visible: size.width > 0 && size.height > 0,
+            window,
+            surface: Some(surface),
+            config,
+            gui,
+            modifiers: Default::default(),
+        }
+    }
+
+    /// A window that only draws egui, presenting with the renderer's device.
+    pub fn new(
+        instance: &wgpu::Instance,
+        window: Arc<Window>,
+        renderer: &Renderer,
+        app_config: &AppConfig,
+    ) -> Result<Self, String> {
+        let surface = instance.create_surface(window.clone()).map_err(|e| e.to_string())?;
+        let capabilities = surface.get_capabilities(renderer.adapter());
+        let size = window.inner_size();
+        let config = app_config.surface_configuration(&capabilities, (size.width.max(1), size.height.max(1)))?;
+        surface.configure(renderer.device(), &config);
+        let gui = GuiRenderer::new(renderer.device(), render_format(&config), None, 1, &window);
+        Ok(Self::from_parts(window, surface, config, gui))
+    }
+
+    /// The format frames are drawn in, see [`render_format`].
+    pub fn format(&self) -> wgpu::TextureFormat {
+        render_format(&self.config)
+    }
+
+    /// Bookkeeping every window needs for `event`. Returns true when egui consumed it.
+    pub fn handle_event(&mut self, device: &wgpu::Device, event: &WindowEvent) -> bool {
+        let consumed = self.gui.handle_input(&self.window, event);
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.factor = *scale_factor;
+                self.gui.ppp(*scale_factor as f32);
+            }
+            WindowEvent::Resized(size) => {
+                self.visible = size.width > 0 && size.height > 0;
+                if self.visible {
+                    self.reconfigure(device);
+                }
+            }
+            _ => {}
+        }
+        consumed
+    }
+
+    /// Matches the surface to the window's current size.
+    pub fn reconfigure(&mut self, device: &wgpu::Device) {
+        let size = self.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.config.width = size.width;
+        self.config.height = size.height;
+        if let Some(surface) = &self.surface {
+            surface.configure(device, &self.config);
+        }
+    }
+
+    /// The next frame, or `None` when this one has to be skipped; a redraw is already requested
+    /// then. Only running out of memory is an error.
+    pub fn current_texture(&mut self, device: &wgpu::Device) -> Result<Option<wgpu::SurfaceTexture>, wgpu::SurfaceError> {
+        let Some(surface) = &self.surface else { return Ok(None) };
+        match surface.get_current_texture() {
+            Ok(frame) => Ok(Some(frame)),
+            // 最小化、切换显示器等情况下交换链失效,重新配置后下一帧再画
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                println!("Surface lost or outdated, reconfiguring");
+                self.reconfigure(device);
+                self.window.request_redraw();
+                Ok(None)
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                println!("Timed out waiting for the next frame, skipping it");
+                self.window.request_redraw();
+                Ok(None)
+            }
+            Err(e @ wgpu::SurfaceError::OutOfMemory) => Err(e),
+        }
+    }
+
+    pub fn present(&mut self, device: &wgpu::Device, frame: wgpu::SurfaceTexture) {
+        let suboptimal = frame.suboptimal;
+        frame.present();
+        if suboptimal {
+            self.reconfigure(device);
+        }
+    }
+
+    pub fn screen_descriptor(&self) -> egui_wgpu::ScreenDescriptor {
+        egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: self.factor as f32,
+        }
+    }
+
+    /// After the device was lost: a new surface and egui renderer on the renderer's new device.
+    pub fn recreate(&mut self, instance: &wgpu::Instance, renderer: &Renderer) -> Result<(), String> {
+        // 先释放旧 surface,同一窗口上同时存在两个 surface 时部分后端会报 NATIVE_WINDOW_IN_USE
+        self.surface = None;
+        let surface = instance
+            .create_surface(self.window.clone())
+            .map_err(|e| format!("failed to recreate the surface: {e}"))?;
+        if !surface.get_capabilities(renderer.adapter()).formats.contains(&self.config.format) {
+            return Err(format!("the recreated surface cannot present {:?}", self.config.format));
+        }
+        self.surface = Some(surface);
+        self.reconfigure(renderer.device());
+        self.gui = GuiRenderer::new(renderer.device(), self.format(), None, self.gui.msaa_samples(), &self.window);
+        self.window.request_redraw();
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+//! Golden-image regression tests. Run with `GOLDEN_UPDATE=1` to re-record the references
+//! in `tests/golden/`; failures leave actual and diff images in `target/golden/`.
+
+use glsl_naga::golden::{render_scene, Golden, Scene};
+use glsl_naga::headless::HeadlessError;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 256;
+
+async fn check_scene(scene: Scene) {
+    let image = match render_scene(scene, WIDTH, HEIGHT).await {
+        Ok(image) => image,
+        Err(HeadlessError::NoAdapter) => {
+            eprintln!("skipping {}: no graphics adapter", scene.name());
+            return;
+        }
+        Err(e) => panic!("{e}"),
+    };
+    let golden = Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"));
+    if let Err(e) = golden.check(scene.name(), &image) {
+        panic!("{e}");
+    }
+}
+
+#[tokio::test]
+async fn pentagon() {
+    check_scene(Scene::Pentagon).await;
+}
+
+#[tokio::test]
+async fn shadow() {
+    check_scene(Scene::Shadow).await;
+}